@@ -0,0 +1,6 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+pub mod module;
+pub mod util;
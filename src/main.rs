@@ -4,17 +4,30 @@
 
 extern crate dotenv;
 
-mod module;
-mod util;
-
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 
 use dotenv::dotenv;
-use module::command::{Command, Type};
+use langmore::module::codec::DEFAULT_MAX_COMMAND_SIZE;
+use langmore::module::compact::Compact;
+use langmore::module::connection::Connection;
+use langmore::module::database::{DEFAULT_COMPACT_RATIO, DEFAULT_MAX_FILE_SIZE};
+use langmore::module::error::Error as LangmoreError;
+use langmore::module::namespace::{
+    Namespaces, DEFAULT_MAX_NAMESPACES, DEFAULT_NAMESPACE,
+};
+use langmore::util::environ::{get_config, parse_config};
+use langmore::util::lock::{read_lock, write_lock};
 use std::env;
 use std::error::Error;
-use util::environ::get_config;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::task;
+use tokio::time;
+
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -31,70 +44,229 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     println!("Listening on: {}", addr);
 
+    // The storage engines shared by all connections, one per namespace
+    let mut namespaces = Namespaces::new(get_config("STORAGE_DIR", "/etc/langmore"));
+
+    namespaces.set_max_file_size(parse_config(
+        "MAX_DATAFILE_SIZE",
+        &DEFAULT_MAX_FILE_SIZE.to_string(),
+    )?);
+
+    namespaces.set_compact_ratio(parse_config(
+        "COMPACT_RATIO",
+        &DEFAULT_COMPACT_RATIO.to_string(),
+    )?);
+
+    // A btree index keeps the keys sorted for range reads
+    namespaces.set_index_type(parse_config("INDEX_TYPE", "hash")?);
+
+    namespaces.set_max_namespaces(parse_config(
+        "MAX_NAMESPACES",
+        &DEFAULT_MAX_NAMESPACES.to_string(),
+    )?);
+
+    // Rebuild the keydir of the default namespace, the others are loaded
+    // by their first SELECT
+    let database = namespaces.open(DEFAULT_NAMESPACE)?;
+
+    {
+        let database = read_lock(&database);
+
+        println!(
+            "Loaded {} records from {}",
+            database.len(),
+            database.get_path()
+        );
+    }
+
+    let namespaces = Arc::new(namespaces);
+
+    // Tells the connections and the background tasks to stop
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    // Each task holds a sender, receiving fails once all of them ended
+    let (done_tx, mut done_rx) = mpsc::channel::<()>(1);
+
+    // Compact the datafiles of a namespace once its dead bytes ratio is reached
+    let compact_interval: u64 = parse_config("COMPACT_INTERVAL", "60")?;
+    let compact_namespaces = Arc::clone(&namespaces);
+    let mut compact_shutdown = shutdown_rx.clone();
+    let compact_done = done_tx.clone();
+
+    tokio::spawn(async move {
+        let _done = compact_done;
+        let mut interval = time::interval(Duration::from_secs(compact_interval));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = compact_shutdown.changed() => return,
+            }
+
+            for (name, db) in compact_namespaces.loaded() {
+                if !read_lock(&db).needs_compaction() {
+                    continue;
+                }
+
+                // The merge reads and writes whole datafiles, it runs off the
+                // async workers
+                match task::spawn_blocking(move || Compact::run(&db)).await {
+                    Ok(Ok(count)) => {
+                        println!("Compacted {} datafiles of {}", count, name)
+                    }
+                    Ok(Err(e)) => eprintln!("Compaction of {} failed: {}", name, e),
+                    Err(e) => eprintln!("Compaction of {} failed: {}", name, e),
+                }
+            }
+        }
+    });
+
+    // Remove the expired keys that are never read again
+    let expire_interval: u64 = parse_config("EXPIRE_INTERVAL", "1")?;
+    let expire_sample: usize = parse_config("EXPIRE_SAMPLE", "20")?;
+    let expire_namespaces = Arc::clone(&namespaces);
+    let mut expire_shutdown = shutdown_rx.clone();
+    let expire_done = done_tx.clone();
+
+    tokio::spawn(async move {
+        let _done = expire_done;
+        let mut interval = time::interval(Duration::from_secs(expire_interval));
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = expire_shutdown.changed() => return,
+            }
+
+            for (name, db) in expire_namespaces.loaded() {
+                let keys = read_lock(&db).sample_expired(expire_sample);
+
+                if keys.is_empty() {
+                    continue;
+                }
+
+                if let Err(e) = write_lock(&db).remove_expired(keys) {
+                    eprintln!("Expiry sweep of {} failed: {}", name, e);
+                }
+            }
+        }
+    });
+
+    // Commands larger than this are rejected
+    let max_command_size: usize =
+        parse_config("MAX_COMMAND_SIZE", &DEFAULT_MAX_COMMAND_SIZE.to_string())?;
+
+    // How long the commands in flight and the background tasks are waited for
+    let shutdown_timeout: u64 = parse_config("SHUTDOWN_TIMEOUT", "30")?;
+
+    let signal = shutdown_signal();
+    tokio::pin!(signal);
+
     loop {
-        let (mut socket, _) = listener.accept().await?;
+        let (mut socket, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut signal => break,
+        };
+
+        let namespaces = Arc::clone(&namespaces);
+        let mut shutdown = shutdown_rx.clone();
+        let done = done_tx.clone();
 
         tokio::spawn(async move {
-            let mut buf = vec![0; 1024];
+            let _done = done;
+            let mut buf = vec![0; 4096];
+            let mut conn = Connection::new(max_command_size);
 
-            // In a loop, read data from the socket and write the data back.
+            // In a loop, read data from the socket and write the replies back.
             loop {
-                let n = socket
-                    .read(&mut buf)
-                    .await
-                    .expect("failed to read data from socket");
+                let read = tokio::select! {
+                    read = socket.read(&mut buf) => read,
+                    _ = shutdown.changed() => return,
+                };
+
+                // A failing client only closes its own connection
+                let n = match read {
+                    Ok(n) => n,
+                    Err(e) => {
+                        eprintln!("Closing {}: {}", peer, LangmoreError::from(e));
+                        return;
+                    }
+                };
 
                 if n == 0 {
+                    let out = conn.finish(&namespaces);
+                    let _ = socket.write_all(&out).await;
                     return;
                 }
 
-                let command = &buf[0..n];
-                let out;
-                let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
-
-                let _s = match std::str::from_utf8(command) {
-                    Ok(v) => {
-                        match Command::from_str(v) {
-                            Ok(v) => {
-                                cmd = v;
-
-                                match *cmd.get_name() {
-                                    Type::Ping => {
-                                        out = String::from("PONG\n");
-                                    }
-                                    Type::Exit => {
-                                        out = String::from("OK\n");
-                                    }
-                                    Type::Get => {
-                                        out = String::from("OK\n");
-                                    }
-                                    Type::Set => {
-                                        out = String::from("OK\n");
-                                    }
-                                    Type::Update => {
-                                        out = String::from("OK\n");
-                                    }
-                                    Type::Delete => {
-                                        out = String::from("OK\n");
-                                    }
-                                    _ => {
-                                        out = String::from("UNKNOWN\n");
-                                    }
-                                }
-                            }
-                            Err(e) => out = format!("{}\n", e),
-                        }
-
-                        println!("{:?}", cmd);
+                conn.feed(&buf[0..n]);
+
+                // Every command received so far is executed before the
+                // replies are written together
+                loop {
+                    let out = conn.pipeline(&namespaces);
+
+                    if out.is_empty() {
+                        break;
                     }
-                    Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-                };
 
-                socket
-                    .write_all(&out.as_str().as_bytes())
-                    .await
-                    .expect("failed to write data to socket");
+                    if let Err(e) = socket.write_all(&out).await {
+                        eprintln!("Closing {}: {}", peer, LangmoreError::from(e));
+                        return;
+                    }
+                }
+
+                if conn.is_closed() {
+                    return;
+                }
             }
         });
     }
+
+    // Stop accepting and wait for the commands in flight to be replied and
+    // for a running compaction or sweep to end
+    println!("Shutting down");
+
+    drop(listener);
+    let _ = shutdown_tx.send(true);
+    drop(done_tx);
+
+    if time::timeout(Duration::from_secs(shutdown_timeout), done_rx.recv())
+        .await
+        .is_err()
+    {
+        eprintln!(
+            "Timed out after {}s waiting for the tasks",
+            shutdown_timeout
+        );
+    }
+
+    // A namespace failing to close does not keep the others from closing
+    for (name, db) in namespaces.loaded() {
+        let mut database = write_lock(&db);
+
+        match database.shutdown() {
+            Ok(_) => println!("Closed {}", database.get_path()),
+            Err(e) => eprintln!("Closing {} failed: {}", name, e),
+        }
+    }
+
+    Ok(())
+}
+
+// Completes once SIGINT or SIGTERM is received
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = terminate.recv() => {},
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
 }
@@ -1,3 +1,298 @@
 // Copyright 2022 Clivern. All rights reserved.
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
+
+use crate::module::entry::{is_expired, now};
+use crate::module::error::Error;
+use crate::module::hint::Hint;
+use crate::module::keydir::{KeyDir, KeyDirEntry};
+use crate::module::reader::Reader;
+
+use std::fs::read_dir;
+
+// Bootup type
+pub struct Bootup {
+    // The database directory holding the datafiles
+    path: String,
+    // Whether the CRC of the scanned entries is checked
+    verify: bool,
+}
+
+// Bootup type methods
+impl Bootup {
+    ///
+    /// Returns a bootup object for the database directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the database directory
+    ///
+    pub fn new<S: Into<String>>(path: S) -> Bootup {
+        Bootup {
+            path: path.into(),
+            verify: true,
+        }
+    }
+
+    ///
+    /// Sets whether the CRC of the scanned entries is checked, on by default
+    ///
+    /// The datafiles left by a clean shutdown can be trusted.
+    ///
+    /// # Arguments
+    ///
+    /// * `verify` - Whether to check the CRC
+    ///
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    ///
+    /// Lists the datafile ids in the database directory
+    ///
+    /// # Returns
+    ///
+    /// * The datafile ids in ascending order
+    /// * Error raised
+    ///
+    pub fn datafile_ids(&self) -> Result<Vec<u32>, Error> {
+        let mut ids: Vec<u32> = Vec::new();
+
+        let dir = match read_dir(self.path.as_str()) {
+            Ok(dir) => dir,
+            Err(_) => return Ok(ids),
+        };
+
+        for item in dir {
+            let item = item?;
+            let name = item.file_name().to_string_lossy().to_string();
+
+            if let Some(id) = name.strip_suffix(".data") {
+                if let Ok(id) = id.parse::<u32>() {
+                    ids.push(id);
+                }
+            }
+        }
+
+        ids.sort_unstable();
+
+        Ok(ids)
+    }
+
+    ///
+    /// Rebuilds the keydir by loading every datafile in file id order
+    ///
+    /// Later entries win over earlier ones, tombstones and expired entries
+    /// remove the key but still raise the highest version seen. A datafile's
+    /// hint file is used when present, otherwise the datafile is scanned up
+    /// to its first corrupted or truncated entry and its hint file is
+    /// written for the next bootup.
+    ///
+    /// # Arguments
+    ///
+    /// * `keydir` - The keydir to fill
+    ///
+    /// # Returns
+    ///
+    /// * The highest datafile id found or zero if there is none
+    /// * Error raised
+    ///
+    pub fn rebuild(&self, keydir: &mut KeyDir) -> Result<u32, Error> {
+        let ids = self.datafile_ids()?;
+        let now = now();
+
+        for id in ids.iter() {
+            match Hint::new(self.hintfile_path(*id)).read() {
+                Ok(entries) => {
+                    for entry in entries.iter() {
+                        let key = entry.get_key().to_vec();
+
+                        keydir.bump_version(entry.get_version());
+
+                        if entry.is_tombstone() || is_expired(entry.get_expire(), now) {
+                            keydir.remove(&key);
+                        } else {
+                            keydir.put(
+                                key,
+                                KeyDirEntry::new(
+                                    *id,
+                                    entry.get_value_size(),
+                                    entry.get_value_pos(),
+                                    entry.get_timestamp(),
+                                    entry.get_expire(),
+                                    entry.get_version(),
+                                ),
+                            );
+                        }
+                    }
+                }
+                Err(_) => {
+                    self.scan(*id, now, keydir)?;
+
+                    if let Err(e) = Hint::from_datafile(self.datafile_path(*id))
+                        .and_then(|entries| {
+                            Hint::new(self.hintfile_path(*id)).write(&entries)
+                        })
+                    {
+                        eprintln!("Unable to write the hint file of {}: {}", id, e);
+                    }
+                }
+            }
+        }
+
+        Ok(ids.last().copied().unwrap_or(0))
+    }
+
+    // Gets the path of a datafile
+    fn datafile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.data", self.path, file_id)
+    }
+
+    // Gets the path of a hint file
+    fn hintfile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.hint", self.path, file_id)
+    }
+
+    // Applies the entries of a datafile to the keydir
+    fn scan(&self, file_id: u32, now: u64, keydir: &mut KeyDir) -> Result<(), Error> {
+        let path = self.datafile_path(file_id);
+        let mut reader = Reader::open(path.to_string())?;
+
+        reader.set_verify(self.verify);
+
+        for item in reader {
+            match item {
+                Ok((offset, entry)) => {
+                    let key = entry.get_key().to_vec();
+
+                    keydir.bump_version(entry.get_version());
+
+                    if entry.is_tombstone() || entry.is_expired(now) {
+                        keydir.remove(&key);
+                    } else {
+                        keydir.put(
+                            key,
+                            KeyDirEntry::new(
+                                file_id,
+                                entry.get_value_size(),
+                                offset + entry.value_offset(),
+                                entry.get_timestamp(),
+                                entry.get_expire(),
+                                entry.get_version(),
+                            ),
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Ignoring the rest of {}: {}", path, e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::entry::LogEntry;
+    use crate::module::hint::HintEntry;
+    use crate::module::writer::Writer;
+
+    #[test]
+    /// test rebuild method
+    fn test_rebuild() {
+        let _ = std::fs::remove_dir_all("cache/bootup_rebuild");
+        let _ = std::fs::create_dir_all("cache/bootup_rebuild");
+
+        let wt: Writer = Writer::new();
+        let first = "cache/bootup_rebuild/1.data".to_string();
+        let second = "cache/bootup_rebuild/2.data".to_string();
+
+        let _ = wt.append_entry(first.to_string(), &LogEntry::new("key1", "value1"));
+        let _ = wt.append_entry(first.to_string(), &LogEntry::new("key2", "value2"));
+        let mut tombstone = LogEntry::tombstone("key1");
+        let mut entry = LogEntry::new("key2", "value3");
+
+        tombstone.set_version(8);
+        entry.set_version(5);
+
+        let _ = wt.append_entry(second.to_string(), &tombstone);
+        let _ = wt.append_entry(second.to_string(), &entry);
+        let _ = wt.append_entry(
+            second.to_string(),
+            &LogEntry::with_expire("key3", "value4", 1652000000),
+        );
+        let _ = wt.append(second.to_string(), "garbage".to_string());
+        let _ = wt.overwrite("cache/bootup_rebuild/10.tmp".to_string(), "".to_string());
+
+        let bootup: Bootup = Bootup::new("cache/bootup_rebuild");
+        let mut keydir: KeyDir = KeyDir::new();
+
+        assert_eq!(bootup.datafile_ids(), Ok(vec![1, 2]));
+        assert_eq!(bootup.rebuild(&mut keydir), Ok(2));
+
+        assert_eq!(keydir.len(), 1);
+        assert!(!keydir.contains("key1"));
+        assert!(!keydir.contains("key3"));
+        assert_eq!(keydir.get_version(), 8);
+
+        let entry = keydir.get("key2").unwrap();
+
+        assert_eq!(entry.get_file_id(), 2);
+        assert_eq!(entry.get_value_sz(), 6);
+        assert_eq!(entry.get_value_pos(), 40 + 36 + 4);
+        assert_eq!(entry.get_version(), 5);
+
+        // The hint files written by the first bootup give the same keydir
+        let mut hinted: KeyDir = KeyDir::new();
+
+        assert!(wt.file_exists("cache/bootup_rebuild/1.hint".to_string()));
+        assert!(wt.file_exists("cache/bootup_rebuild/2.hint".to_string()));
+        assert_eq!(bootup.rebuild(&mut hinted), Ok(2));
+        assert_eq!(hinted.len(), 1);
+        assert_eq!(hinted.get("key2"), keydir.get("key2"));
+        assert_eq!(hinted.get_version(), 8);
+    }
+
+    #[test]
+    /// test rebuild prefers hint files and falls back on corrupted ones
+    fn test_rebuild_hint() {
+        let _ = std::fs::remove_dir_all("cache/bootup_hint");
+        let _ = std::fs::create_dir_all("cache/bootup_hint");
+
+        let wt: Writer = Writer::new();
+        let path = "cache/bootup_hint/1.data".to_string();
+
+        let _ = wt.append_entry(path.to_string(), &LogEntry::new("key1", "value1"));
+
+        // A hint file that disagrees with the datafile shows which one was used
+        let _ = Hint::new("cache/bootup_hint/1.hint")
+            .write(&[HintEntry::new(1652000000, 0, 1, 3, 7, "key9")]);
+
+        let bootup: Bootup = Bootup::new("cache/bootup_hint");
+        let mut keydir: KeyDir = KeyDir::new();
+
+        assert_eq!(bootup.rebuild(&mut keydir), Ok(1));
+        assert!(keydir.contains("key9"));
+        assert!(!keydir.contains("key1"));
+
+        let _ = wt.append("cache/bootup_hint/1.hint".to_string(), "x".to_string());
+        let mut keydir: KeyDir = KeyDir::new();
+
+        assert_eq!(bootup.rebuild(&mut keydir), Ok(1));
+        assert!(keydir.contains("key1"));
+        assert!(!keydir.contains("key9"));
+    }
+
+    #[test]
+    /// test rebuild on a missing directory
+    fn test_rebuild_empty() {
+        let bootup: Bootup = Bootup::new("cache/bootup_missing");
+        let mut keydir: KeyDir = KeyDir::new();
+
+        assert_eq!(bootup.rebuild(&mut keydir), Ok(0));
+        assert!(keydir.is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::error::Error;
+use crate::module::resp::parse;
+
+/// The default maximum size of a command in bytes
+pub const DEFAULT_MAX_COMMAND_SIZE: usize = 1048576;
+
+#[derive(Debug, PartialEq, Clone)]
+// Request type
+pub enum Request {
+    // A plain text command line without its line ending
+    Line(Vec<u8>),
+    // The arguments of a RESP array frame
+    Args(Vec<Vec<u8>>),
+}
+
+#[derive(Debug)]
+// Codec type
+pub struct Codec {
+    // The received bytes not framed yet
+    buffer: Vec<u8>,
+    // The maximum size of a command in bytes
+    max_size: usize,
+    // Whether the connection speaks RESP, decided by its first byte
+    resp: Option<bool>,
+    // Whether the rest of an oversized line is being dropped
+    skipping: bool,
+}
+
+// Codec type methods
+impl Codec {
+    ///
+    /// Returns a codec for a new connection
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - The maximum size of a command in bytes
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::codec::{Codec, DEFAULT_MAX_COMMAND_SIZE};
+    ///
+    /// let codec: Codec = Codec::new(DEFAULT_MAX_COMMAND_SIZE);
+    /// ```
+    ///
+    pub fn new(max_size: usize) -> Codec {
+        Codec {
+            buffer: Vec::new(),
+            max_size,
+            resp: None,
+            skipping: false,
+        }
+    }
+
+    ///
+    /// Whether the connection speaks RESP
+    ///
+    pub fn is_resp(&self) -> bool {
+        self.resp == Some(true)
+    }
+
+    ///
+    /// Appends bytes read from the connection
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The received bytes
+    ///
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.resp.is_none() && !data.is_empty() {
+            self.resp = Some(data[0] == b'*');
+        }
+
+        self.buffer.extend_from_slice(data);
+    }
+
+    ///
+    /// Takes the next complete request out of the buffered bytes
+    ///
+    /// A plain text command ends with a line feed. A command larger than the
+    /// maximum size is dropped with an error, in plain text the framing
+    /// resumes after its line feed while a RESP connection can not recover
+    /// and should be closed.
+    ///
+    /// # Returns
+    ///
+    /// * The request or None if more bytes are needed
+    /// * Error raised
+    ///
+    pub fn decode(&mut self) -> Result<Option<Request>, Error> {
+        if self.is_resp() {
+            return self.decode_frame();
+        }
+
+        loop {
+            let end = self.buffer.iter().position(|c| *c == b'\n');
+
+            if self.skipping {
+                match end {
+                    Some(end) => {
+                        self.buffer.drain(0..=end);
+                        self.skipping = false;
+                        continue;
+                    }
+                    None => {
+                        self.buffer.clear();
+                        return Ok(None);
+                    }
+                }
+            }
+
+            return match end {
+                Some(end) if end > self.max_size => {
+                    self.buffer.drain(0..=end);
+                    Err(self.too_large())
+                }
+                Some(end) => {
+                    let mut line: Vec<u8> = self.buffer.drain(0..=end).collect();
+
+                    line.pop();
+
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+
+                    Ok(Some(Request::Line(line)))
+                }
+                None if self.buffer.len() > self.max_size => {
+                    self.buffer.clear();
+                    self.skipping = true;
+                    Err(self.too_large())
+                }
+                None => Ok(None),
+            };
+        }
+    }
+
+    ///
+    /// Takes the unterminated plain text command left when the connection
+    /// is closed
+    ///
+    /// # Returns
+    ///
+    /// * The request or None if nothing is left
+    ///
+    pub fn finish(&mut self) -> Option<Request> {
+        if self.is_resp() || self.skipping || self.buffer.is_empty() {
+            return None;
+        }
+
+        let mut line: Vec<u8> = self.buffer.drain(..).collect();
+
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        Some(Request::Line(line))
+    }
+
+    // Takes the next RESP frame out of the buffered bytes
+    fn decode_frame(&mut self) -> Result<Option<Request>, Error> {
+        match parse(&self.buffer) {
+            Ok(Some((_, used))) if used > self.max_size => {
+                self.buffer.clear();
+                Err(self.too_large())
+            }
+            Ok(Some((args, used))) => {
+                self.buffer.drain(0..used);
+                Ok(Some(Request::Args(args)))
+            }
+            Ok(None) if self.buffer.len() > self.max_size => {
+                self.buffer.clear();
+                Err(self.too_large())
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                self.buffer.clear();
+                Err(e)
+            }
+        }
+    }
+
+    // The error raised for an oversized command
+    fn too_large(&self) -> Error {
+        Error::Protocol(format!(
+            "Command exceeds the maximum size of {} bytes",
+            self.max_size
+        ))
+    }
+}
+
+#[test]
+fn test_codec_lines() {
+    let mut codec: Codec = Codec::new(16);
+
+    codec.feed(b"SET key1 val");
+
+    assert!(!codec.is_resp());
+    assert_eq!(codec.decode(), Ok(None));
+
+    codec.feed(b"ue1\r\nGET key1\nPI");
+
+    assert_eq!(
+        codec.decode(),
+        Ok(Some(Request::Line(b"SET key1 value1".to_vec())))
+    );
+    assert_eq!(
+        codec.decode(),
+        Ok(Some(Request::Line(b"GET key1".to_vec())))
+    );
+    assert_eq!(codec.decode(), Ok(None));
+
+    codec.feed(b"NG\nSET key2 a-very-long");
+
+    assert_eq!(codec.decode(), Ok(Some(Request::Line(b"PING".to_vec()))));
+    assert!(codec.decode().is_err());
+
+    codec.feed(b"-value\nGET key2\nSET key3 a-very-long-value\nEXIT");
+
+    assert_eq!(
+        codec.decode(),
+        Ok(Some(Request::Line(b"GET key2".to_vec())))
+    );
+    assert!(codec.decode().is_err());
+    assert_eq!(codec.decode(), Ok(None));
+    assert_eq!(codec.finish(), Some(Request::Line(b"EXIT".to_vec())));
+    assert_eq!(codec.finish(), None);
+}
+
+#[test]
+fn test_codec_frames() {
+    let mut codec: Codec = Codec::new(32);
+
+    codec.feed(b"*2\r\n$3\r\nGET\r\n$4\r\nke");
+
+    assert!(codec.is_resp());
+    assert_eq!(codec.decode(), Ok(None));
+
+    codec.feed(b"y1\r\n*1\r\n$4\r\nPING\r\n");
+
+    assert_eq!(
+        codec.decode(),
+        Ok(Some(Request::Args(vec![b"GET".to_vec(), b"key1".to_vec()])))
+    );
+    assert_eq!(
+        codec.decode(),
+        Ok(Some(Request::Args(vec![b"PING".to_vec()])))
+    );
+    assert_eq!(codec.decode(), Ok(None));
+
+    codec.feed(b"*1\r\n$40\r\n0123456789012345678901234567890123456789\r\n");
+
+    assert!(codec.decode().is_err());
+    assert_eq!(codec.decode(), Ok(None));
+    assert_eq!(codec.finish(), None);
+}
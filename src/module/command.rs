@@ -2,7 +2,12 @@
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
 
-#[derive(Debug, PartialEq)]
+use crate::module::error::Error;
+
+/// The number of keys a `SCAN` examines when no `COUNT` is given
+pub const DEFAULT_SCAN_COUNT: i64 = 10;
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Type {
     Set,
     Get,
@@ -10,100 +15,357 @@ pub enum Type {
     Delete,
     Ping,
     Exit,
+    Compact,
+    Snapshot,
+    Snapshots,
+    Restore,
+    Multi,
+    Exec,
+    Discard,
+    Ttl,
+    Expire,
+    Persist,
+    Stats,
+    Exists,
+    MGet,
+    MSet,
+    MDelete,
+    SetNx,
+    Cas,
+    GetV,
+    SetV,
+    Scan,
+    Keys,
+    Range,
+    RevRange,
+    Incr,
+    Decr,
+    IncrBy,
+    DecrBy,
+    Append,
+    StrLen,
+    GetRange,
+    Select,
     Unknown,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Command {
-    key: String,
-    value: String,
+    key: Vec<u8>,
+    value: Vec<u8>,
     expire: i64,
     name: Type,
+    // The keys, or the keys and values, of a multi-key command
+    args: Vec<Vec<u8>>,
+    // The version a conditional write expects
+    version: u64,
 }
 
 impl Command {
-    pub fn new<S: Into<String>>(key: S, value: S, expire: i64, name: Type) -> Command {
+    pub fn new<S: Into<Vec<u8>>>(key: S, value: S, expire: i64, name: Type) -> Command {
         Command {
             key: key.into(),
             value: value.into(),
-            expire: expire,
-            name: name,
+            expire,
+            name,
+            args: Vec::new(),
+            version: 0,
         }
     }
 
-    pub fn from_str<S: Into<String>>(cmd: S) -> Result<Command, String> {
-        let name_val: Type;
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str<S: Into<String>>(cmd: S) -> Result<Command, Error> {
+        Command::from_bytes(cmd.into().as_bytes())
+    }
+
+    ///
+    /// Parses a command line into a command
+    ///
+    /// Arguments are separated by whitespace, an argument holding
+    /// whitespace, quotes or arbitrary bytes is written between double
+    /// quotes with `\"`, `\\`, `\n`, `\r`, `\t` and `\xHH` escapes.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command line bytes
+    ///
+    /// # Returns
+    ///
+    /// * The parsed command
+    /// * Error raised
+    ///
+    pub fn from_bytes(cmd: &[u8]) -> Result<Command, Error> {
+        let cmd_str = String::from_utf8_lossy(cmd)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+
+        Command::build(split_args(cmd)?, cmd_str)
+    }
+
+    ///
+    /// Builds a command from its already split arguments
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - The command name followed by its arguments
+    ///
+    /// # Returns
+    ///
+    /// * The parsed command
+    /// * Error raised
+    ///
+    pub fn from_args(items: Vec<Vec<u8>>) -> Result<Command, Error> {
+        let cmd_str = items
+            .iter()
+            .map(|item| quote(item))
+            .collect::<Vec<String>>()
+            .join(" ");
 
-        let cmd_str = cmd.into();
+        Command::build(items, cmd_str)
+    }
+
+    // Matches the command name and checks its arguments
+    fn build(items: Vec<Vec<u8>>, cmd_str: String) -> Result<Command, Error> {
+        let invalid =
+            || Error::Protocol(format!("Invalid command {cmd}", cmd = cmd_str));
 
-        let command = cmd_str.trim().to_string();
-        let mut items: Vec<&str> = command.split(' ').collect();
+        let name = match items.first() {
+            Some(name) => String::from_utf8_lossy(name).to_string(),
+            None => String::new(),
+        };
 
         // Match the command
-        match items[0].to_uppercase().as_str() {
-            "SET" => {
-                name_val = Type::Set;
-            }
-            "GET" => {
-                name_val = Type::Get;
-            }
-            "UPDATE" => {
-                name_val = Type::Update;
-            }
-            "DELETE" => {
-                name_val = Type::Delete;
-            }
-            "PING" => {
-                name_val = Type::Ping;
-            }
-            "EXIT" => {
-                name_val = Type::Exit;
-            }
-            _ => {
-                name_val = Type::Unknown;
-            }
-        }
+        let name_val = match name.to_uppercase().as_str() {
+            "SET" => Type::Set,
+            "GET" => Type::Get,
+            "UPDATE" => Type::Update,
+            "DELETE" => Type::Delete,
+            "PING" => Type::Ping,
+            "EXIT" => Type::Exit,
+            "COMPACT" => Type::Compact,
+            "SNAPSHOT" => Type::Snapshot,
+            "SNAPSHOTS" => Type::Snapshots,
+            "RESTORE" => Type::Restore,
+            "MULTI" => Type::Multi,
+            "EXEC" => Type::Exec,
+            "DISCARD" => Type::Discard,
+            "TTL" => Type::Ttl,
+            "EXPIRE" => Type::Expire,
+            "PERSIST" => Type::Persist,
+            "STATS" => Type::Stats,
+            "EXISTS" => Type::Exists,
+            "MGET" => Type::MGet,
+            "MSET" => Type::MSet,
+            "MDELETE" => Type::MDelete,
+            "SETNX" => Type::SetNx,
+            "CAS" => Type::Cas,
+            "GETV" => Type::GetV,
+            "SETV" => Type::SetV,
+            "SCAN" => Type::Scan,
+            "KEYS" => Type::Keys,
+            "RANGE" => Type::Range,
+            "REVRANGE" => Type::RevRange,
+            "INCR" => Type::Incr,
+            "DECR" => Type::Decr,
+            "INCRBY" => Type::IncrBy,
+            "DECRBY" => Type::DecrBy,
+            "APPEND" => Type::Append,
+            "STRLEN" => Type::StrLen,
+            "GETRANGE" => Type::GetRange,
+            "SELECT" => Type::Select,
+            _ => Type::Unknown,
+        };
 
         // If an invalid command, raise an error
         if name_val == Type::Unknown {
-            return Err(format!(
+            return Err(Error::Protocol(format!(
                 "Invalid command name `{name_val}`",
-                name_val = items[0]
-            ));
+                name_val = name
+            )));
         }
 
-        // Fix items
-        while items.len() < 4 {
-            items.push("");
+        // The number of required arguments after the command name
+        let arity = match name_val {
+            Type::Get
+            | Type::Delete
+            | Type::Restore
+            | Type::Ttl
+            | Type::Persist
+            | Type::Exists
+            | Type::MGet
+            | Type::MDelete
+            | Type::GetV
+            | Type::Scan
+            | Type::Incr
+            | Type::Decr
+            | Type::StrLen
+            | Type::Select => 1,
+            Type::Set
+            | Type::Update
+            | Type::Expire
+            | Type::MSet
+            | Type::SetNx
+            | Type::Range
+            | Type::RevRange
+            | Type::IncrBy
+            | Type::DecrBy
+            | Type::Append => 2,
+            Type::Cas | Type::SetV | Type::GetRange => 3,
+            _ => 0,
+        };
+
+        if items.len() <= arity {
+            return Err(invalid());
         }
 
-        if items[3] == "" {
-            items[3] = "0"
+        let arg = |i: usize| items.get(i).cloned().unwrap_or_default();
+
+        // `MSET $key $value [$key $value ...]` takes whole pairs
+        if let Type::MGet | Type::MSet | Type::MDelete = name_val {
+            if name_val == Type::MSet && items.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+
+            let mut cmd = Command::new(arg(1), Vec::new(), 0, name_val);
+
+            cmd.set_args(items[1..].to_vec());
+
+            return Ok(cmd);
         }
 
-        if ((name_val == Type::Get) || (name_val == Type::Delete)) && (items[1] == "") {
-            return Err(format!("Invalid command {cmd}", cmd = cmd_str));
+        // `SCAN $cursor [MATCH $pattern] [COUNT $count]` carries the key the
+        // scan resumes at and keeps its pattern, empty for none, and its count
+        // in the args. `RANGE $start $end [LIMIT $limit]` keeps its end key
+        // and its limit, zero for none, in the args
+        if let Type::Scan | Type::Range | Type::RevRange = name_val {
+            let (mut cmd, mut args, options) = match name_val {
+                Type::Scan => {
+                    let start = parse_cursor(&arg(1)).ok_or_else(invalid)?;
+
+                    (
+                        Command::new(start, Vec::new(), 0, name_val),
+                        vec![Vec::new(), DEFAULT_SCAN_COUNT.to_string().into_bytes()],
+                        &items[2..],
+                    )
+                }
+                _ => (
+                    Command::new(arg(1), Vec::new(), 0, name_val),
+                    vec![arg(2), b"0".to_vec()],
+                    &items[3..],
+                ),
+            };
+
+            if !options.len().is_multiple_of(2) {
+                return Err(invalid());
+            }
+
+            for option in options.chunks(2) {
+                let name = String::from_utf8_lossy(&option[0]).to_uppercase();
+                let number = String::from_utf8_lossy(&option[1])
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|number| *number > 0);
+
+                match (cmd.get_name(), name.as_str(), number) {
+                    (Type::Scan, "MATCH", _) => args[0] = option[1].to_vec(),
+                    (Type::Scan, "COUNT", Some(_))
+                    | (Type::Range | Type::RevRange, "LIMIT", Some(_)) => {
+                        args[1] = option[1].to_vec()
+                    }
+                    _ => return Err(invalid()),
+                }
+            }
+
+            cmd.set_args(args);
+
+            return Ok(cmd);
+        }
+
+        // The counter commands carry their signed amount in the value field
+        if let Type::Incr | Type::Decr | Type::IncrBy | Type::DecrBy = name_val {
+            let amount = match name_val {
+                Type::Incr | Type::Decr => Some(1),
+                _ => String::from_utf8_lossy(&arg(2)).parse::<i64>().ok(),
+            };
+
+            let amount = match name_val {
+                Type::Decr | Type::DecrBy => amount.and_then(i64::checked_neg),
+                _ => amount,
+            };
+
+            return match amount {
+                Some(amount) if items.len() == arity + 1 => Ok(Command::new(
+                    arg(1),
+                    amount.to_string().into_bytes(),
+                    0,
+                    name_val,
+                )),
+                _ => Err(invalid()),
+            };
         }
 
-        if ((name_val == Type::Set) || (name_val == Type::Update))
-            && ((items[1] == "") || (items[2] == ""))
-        {
-            return Err(format!("Invalid command {cmd}", cmd = cmd_str));
+        // These take no optional arguments and `GETRANGE $key $start $end`
+        // keeps its offsets in the args
+        if let Type::Append | Type::StrLen | Type::GetRange | Type::Select = name_val {
+            if items.len() != arity + 1 {
+                return Err(invalid());
+            }
+
+            let mut cmd = Command::new(arg(1), Vec::new(), 0, name_val);
+
+            match *cmd.get_name() {
+                Type::Append => cmd.set_value(arg(2)),
+                Type::GetRange => {
+                    for offset in items[2..].iter() {
+                        if String::from_utf8_lossy(offset).parse::<i64>().is_err() {
+                            return Err(invalid());
+                        }
+                    }
+
+                    cmd.set_args(items[2..].to_vec());
+                }
+                _ => {}
+            }
+
+            return Ok(cmd);
         }
 
-        Ok(Command::new(
-            items[1],
-            items[2],
-            items[3].to_string().parse::<i64>().unwrap(),
-            name_val,
-        ))
+        // `EXPIRE $key $secs` carries the seconds in the expire field while
+        // `CAS $key $old $new` and `SETV $key $version $value` take their
+        // value third
+        let (value, expire) = match name_val {
+            Type::Expire => (Vec::new(), arg(2)),
+            Type::Cas | Type::SetV => (arg(3), arg(4)),
+            _ => (arg(2), arg(3)),
+        };
+
+        let expire = match String::from_utf8_lossy(&expire).as_ref() {
+            "" => 0,
+            expire => match expire.parse::<i64>() {
+                Ok(expire) if expire >= 0 => expire,
+                _ => return Err(invalid()),
+            },
+        };
+
+        let mut cmd = Command::new(arg(1), value, expire, name_val);
+
+        match *cmd.get_name() {
+            Type::Cas => cmd.set_args(vec![arg(2)]),
+            Type::SetV => match String::from_utf8_lossy(&arg(2)).parse::<u64>() {
+                Ok(version) => cmd.set_version(version),
+                Err(_) => return Err(invalid()),
+            },
+            _ => {}
+        }
+
+        Ok(cmd)
     }
 
-    pub fn get_key(&self) -> &String {
+    pub fn get_key(&self) -> &Vec<u8> {
         &self.key
     }
 
-    pub fn get_value(&self) -> &String {
+    pub fn get_value(&self) -> &Vec<u8> {
         &self.value
     }
 
@@ -115,11 +377,37 @@ impl Command {
         &self.name
     }
 
-    pub fn set_key<S: Into<String>>(&mut self, key: S) {
+    ///
+    /// Parses a numeric argument
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The argument position
+    ///
+    /// # Returns
+    ///
+    /// * The number or zero if the argument is missing or not a number
+    ///
+    pub fn get_number(&self, index: usize) -> i64 {
+        self.args
+            .get(index)
+            .and_then(|arg| String::from_utf8_lossy(arg).parse::<i64>().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn get_args(&self) -> &Vec<Vec<u8>> {
+        &self.args
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set_key<S: Into<Vec<u8>>>(&mut self, key: S) {
         self.key = key.into()
     }
 
-    pub fn set_value<S: Into<String>>(&mut self, value: S) {
+    pub fn set_value<S: Into<Vec<u8>>>(&mut self, value: S) {
         self.value = value.into()
     }
 
@@ -130,6 +418,226 @@ impl Command {
     pub fn set_name(&mut self, name: Type) {
         self.name = name
     }
+
+    pub fn set_args(&mut self, args: Vec<Vec<u8>>) {
+        self.args = args
+    }
+
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version
+    }
+}
+
+///
+/// Splits a command line into its arguments
+///
+/// # Arguments
+///
+/// * `line` - The command line bytes
+///
+/// # Returns
+///
+/// * The unquoted arguments
+/// * Error raised if a quoted argument is malformed
+///
+pub fn split_args(line: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut items: Vec<Vec<u8>> = Vec::new();
+    let mut pos = 0;
+
+    while pos < line.len() {
+        if line[pos].is_ascii_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let mut item: Vec<u8> = Vec::new();
+
+        if line[pos] != b'"' {
+            while pos < line.len() && !line[pos].is_ascii_whitespace() {
+                item.push(line[pos]);
+                pos += 1;
+            }
+
+            items.push(item);
+            continue;
+        }
+
+        pos += 1;
+
+        loop {
+            match line.get(pos) {
+                None => {
+                    return Err(Error::Protocol(
+                        "Unbalanced quotes in command".to_string(),
+                    ))
+                }
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let byte = match line.get(pos + 1) {
+                        Some(b'n') => b'\n',
+                        Some(b'r') => b'\r',
+                        Some(b't') => b'\t',
+                        Some(b'x') => {
+                            let hex = line.get(pos + 2..pos + 4).unwrap_or_default();
+
+                            pos += 2;
+
+                            std::str::from_utf8(hex)
+                                .ok()
+                                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                                .ok_or_else(|| {
+                                    Error::Protocol(
+                                        "Invalid escape in command".to_string(),
+                                    )
+                                })?
+                        }
+                        Some(byte) => *byte,
+                        None => {
+                            return Err(Error::Protocol(
+                                "Unbalanced quotes in command".to_string(),
+                            ))
+                        }
+                    };
+
+                    item.push(byte);
+                    pos += 2;
+                }
+                Some(byte) => {
+                    item.push(*byte);
+                    pos += 1;
+                }
+            }
+        }
+
+        pos += 1;
+
+        if pos < line.len() && !line[pos].is_ascii_whitespace() {
+            return Err(Error::Protocol(
+                "Closing quote must be followed by a space".to_string(),
+            ));
+        }
+
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+///
+/// Quotes a key or a value so it can be written back on a single line
+///
+/// Text without whitespace, quotes or backslashes is returned as is,
+/// anything else is quoted with the escapes `split_args` understands.
+///
+/// # Arguments
+///
+/// * `value` - The bytes to quote
+///
+/// # Returns
+///
+/// * The quoted text
+///
+pub fn quote(value: &[u8]) -> String {
+    let text = std::str::from_utf8(value).ok();
+
+    if let Some(text) = text {
+        let bare = !text.is_empty()
+            && text
+                .chars()
+                .all(|c| !c.is_whitespace() && !c.is_control() && c != '"' && c != '\\');
+
+        if bare {
+            return text.to_string();
+        }
+    }
+
+    quoted(value)
+}
+
+///
+/// Quotes a key or a value even when it could be written as is
+///
+/// # Arguments
+///
+/// * `value` - The bytes to quote
+///
+/// # Returns
+///
+/// * The quoted text
+///
+pub fn quoted(value: &[u8]) -> String {
+    let text = std::str::from_utf8(value).ok();
+    let mut out = String::from("\"");
+
+    match text {
+        Some(text) => text.chars().for_each(|c| escape(c, &mut out)),
+        None => value.iter().for_each(|byte| match byte.is_ascii() {
+            true => escape(*byte as char, &mut out),
+            false => out.push_str(&format!("\\x{:02x}", byte)),
+        }),
+    }
+
+    out.push('"');
+    out
+}
+
+///
+/// Returns the cursor a `SCAN` replies for the key its next page starts at
+///
+/// The cursor is the key in hexadecimal, `0` starts a scan and ends it.
+///
+/// # Arguments
+///
+/// * `next` - The key the next page starts at or None if the scan is over
+///
+/// # Returns
+///
+/// * The cursor text
+///
+pub fn cursor(next: Option<&[u8]>) -> String {
+    match next {
+        Some(key) if !key.is_empty() => {
+            key.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }
+        _ => "0".to_string(),
+    }
+}
+
+// Decodes a cursor into the key a scan resumes at, `0` resumes at the first key
+fn parse_cursor(cursor: &[u8]) -> Option<Vec<u8>> {
+    if cursor == b"0" {
+        return Some(Vec::new());
+    }
+
+    if cursor.is_empty() || !cursor.len().is_multiple_of(2) {
+        return None;
+    }
+
+    cursor
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        })
+        .collect()
+}
+
+// Appends a character escaped for a quoted argument
+fn escape(c: char, out: &mut String) {
+    match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c if c.is_control() => {
+            for byte in c.to_string().bytes() {
+                out.push_str(&format!("\\x{:02x}", byte));
+            }
+        }
+        c => out.push(c),
+    }
 }
 
 #[test]
@@ -141,34 +649,28 @@ fn test_set_command() {
     cmd.set_expire(0);
     cmd.set_name(Type::Set);
 
-    assert_eq!(*cmd.get_key(), "item1".to_string());
-    assert_eq!(*cmd.get_value(), "value1".to_string());
+    assert_eq!(*cmd.get_key(), b"item1".to_vec());
+    assert_eq!(*cmd.get_value(), b"value1".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Set);
 
     // Test `SET $key $value $expire` command
-    match Command::from_str("SET item2 value2") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("SET item2 value2") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "item2".to_string());
-    assert_eq!(*cmd.get_value(), "value2".to_string());
+    assert_eq!(*cmd.get_key(), b"item2".to_vec());
+    assert_eq!(*cmd.get_value(), b"value2".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Set);
 
     // Test `SET $key $value $expire` command
-    match Command::from_str("SET item2 value2 160") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("SET item2 value2 160") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "item2".to_string());
-    assert_eq!(*cmd.get_value(), "value2".to_string());
+    assert_eq!(*cmd.get_key(), b"item2".to_vec());
+    assert_eq!(*cmd.get_value(), b"value2".to_vec());
     assert_eq!(*cmd.get_expire(), 160);
     assert_eq!(*cmd.get_name(), Type::Set);
 }
@@ -178,15 +680,12 @@ fn test_get_command() {
     let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
 
     // Test `GET $key` command
-    match Command::from_str("GET item2") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("GET item2") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "item2".to_string());
-    assert_eq!(*cmd.get_value(), "".to_string());
+    assert_eq!(*cmd.get_key(), b"item2".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Get);
 }
@@ -196,15 +695,12 @@ fn test_delete_command() {
     let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
 
     // Test `DELETE $key` command
-    match Command::from_str("DELETE item2") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("DELETE item2") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "item2".to_string());
-    assert_eq!(*cmd.get_value(), "".to_string());
+    assert_eq!(*cmd.get_key(), b"item2".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Delete);
 }
@@ -214,15 +710,12 @@ fn test_ping_command() {
     let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
 
     // Test `PING` command
-    match Command::from_str("PING") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("PING") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "".to_string());
-    assert_eq!(*cmd.get_value(), "".to_string());
+    assert_eq!(*cmd.get_key(), b"".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Ping);
 }
@@ -232,39 +725,297 @@ fn test_exit_command() {
     let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
 
     // Test `EXIT` command
-    match Command::from_str("EXIT") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("EXIT") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "".to_string());
-    assert_eq!(*cmd.get_value(), "".to_string());
+    assert_eq!(*cmd.get_key(), b"".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Exit);
 
     // Test `exit` command
-    match Command::from_str("exit") {
-        Ok(v) => {
-            cmd = v;
-        }
-        Err(_) => {}
+    if let Ok(v) = Command::from_str("exit") {
+        cmd = v;
     }
 
-    assert_eq!(*cmd.get_key(), "".to_string());
-    assert_eq!(*cmd.get_value(), "".to_string());
+    assert_eq!(*cmd.get_key(), b"".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
     assert_eq!(*cmd.get_expire(), 0);
     assert_eq!(*cmd.get_name(), Type::Exit);
 }
 
+#[test]
+fn test_compact_command() {
+    let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
+
+    // Test `COMPACT` command
+    if let Ok(v) = Command::from_str("COMPACT") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Compact);
+}
+
+#[test]
+fn test_snapshot_command() {
+    let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
+
+    // Test `SNAPSHOT $name` command
+    if let Ok(v) = Command::from_str("SNAPSHOT backup") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"backup".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Snapshot);
+
+    // Test `SNAPSHOTS` command
+    if let Ok(v) = Command::from_str("SNAPSHOTS") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Snapshots);
+
+    // Test `RESTORE $name` command
+    if let Ok(v) = Command::from_str("RESTORE backup") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"backup".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Restore);
+    assert_eq!(
+        Command::from_str("RESTORE").unwrap_err(),
+        Error::Protocol("Invalid command RESTORE".to_string())
+    );
+}
+
+#[test]
+fn test_transaction_command() {
+    let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
+
+    // Test `MULTI` command
+    if let Ok(v) = Command::from_str("MULTI") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_name(), Type::Multi);
+
+    // Test `EXEC` command
+    if let Ok(v) = Command::from_str("EXEC") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_name(), Type::Exec);
+
+    // Test `DISCARD` command
+    if let Ok(v) = Command::from_str("discard") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_name(), Type::Discard);
+}
+
+#[test]
+fn test_multi_key_command() {
+    let cmd = Command::from_str("MSET key1 value1 \"key 2\" value2").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::MSet);
+    assert_eq!(*cmd.get_key(), b"key1".to_vec());
+    assert_eq!(
+        *cmd.get_args(),
+        vec![
+            b"key1".to_vec(),
+            b"value1".to_vec(),
+            b"key 2".to_vec(),
+            b"value2".to_vec()
+        ]
+    );
+
+    let cmd = Command::from_str("mget key1 key2 key3").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::MGet);
+    assert_eq!(cmd.get_args().len(), 3);
+    assert_eq!(
+        Command::from_str("MDELETE key1").unwrap().get_name(),
+        &Type::MDelete
+    );
+    assert!(Command::from_str("MGET").is_err());
+    assert!(Command::from_str("MDELETE").is_err());
+    assert!(Command::from_str("MSET key1").is_err());
+    assert!(Command::from_str("MSET key1 value1 key2").is_err());
+    assert!(Command::from_str("SET key1 value1")
+        .unwrap()
+        .get_args()
+        .is_empty());
+}
+
+#[test]
+fn test_conditional_command() {
+    let cmd = Command::from_str("CAS key1 old new 60").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::Cas);
+    assert_eq!(*cmd.get_args(), vec![b"old".to_vec()]);
+    assert_eq!(*cmd.get_value(), b"new".to_vec());
+    assert_eq!(*cmd.get_expire(), 60);
+
+    let cmd = Command::from_str("SETV key1 7 value1").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::SetV);
+    assert_eq!(cmd.get_version(), 7);
+    assert_eq!(*cmd.get_value(), b"value1".to_vec());
+
+    let cmd = Command::from_str("setnx key1 value1").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::SetNx);
+    assert_eq!(*cmd.get_value(), b"value1".to_vec());
+    assert_eq!(
+        Command::from_str("GETV key1").unwrap().get_name(),
+        &Type::GetV
+    );
+    assert!(Command::from_str("SETNX key1").is_err());
+    assert!(Command::from_str("CAS key1 old").is_err());
+    assert!(Command::from_str("SETV key1 -1 value1").is_err());
+    assert!(Command::from_str("GETV").is_err());
+}
+
+#[test]
+fn test_scan_command() {
+    let cmd = Command::from_str("SCAN 0").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::Scan);
+    assert!(cmd.get_key().is_empty());
+    assert_eq!(cmd.get_args(), &vec![Vec::new(), b"10".to_vec()]);
+
+    let cmd = Command::from_str("scan 6b657931 match key* COUNT 100").unwrap();
+
+    assert_eq!(*cmd.get_key(), b"key1".to_vec());
+    assert_eq!(cmd.get_args(), &vec![b"key*".to_vec(), b"100".to_vec()]);
+    assert_eq!(cursor(Some(b"key1")), "6b657931");
+    assert_eq!(cursor(None), "0");
+    assert!(Command::from_str("SCAN").is_err());
+    assert!(Command::from_str("SCAN 6b6").is_err());
+    assert!(Command::from_str("SCAN zz").is_err());
+    assert!(Command::from_str("SCAN 0 COUNT 0").is_err());
+    assert!(Command::from_str("SCAN 0 MATCH").is_err());
+    assert!(Command::from_str("SCAN 0 TYPE string").is_err());
+
+    let cmd = Command::from_str("KEYS user:").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::Keys);
+    assert_eq!(*cmd.get_key(), b"user:".to_vec());
+    assert!(Command::from_str("KEYS").unwrap().get_key().is_empty());
+}
+
+#[test]
+fn test_range_command() {
+    let cmd = Command::from_str("RANGE events:1 events:9").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::Range);
+    assert_eq!(*cmd.get_key(), b"events:1".to_vec());
+    assert_eq!(cmd.get_args(), &vec![b"events:9".to_vec(), b"0".to_vec()]);
+
+    let cmd = Command::from_str("revrange events:1 events:9 limit 5").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::RevRange);
+    assert_eq!(cmd.get_args(), &vec![b"events:9".to_vec(), b"5".to_vec()]);
+    assert!(Command::from_str("RANGE events:1").is_err());
+    assert!(Command::from_str("RANGE events:1 events:9 LIMIT").is_err());
+    assert!(Command::from_str("RANGE events:1 events:9 LIMIT 0").is_err());
+    assert!(Command::from_str("RANGE events:1 events:9 COUNT 5").is_err());
+}
+
+#[test]
+fn test_counter_command() {
+    let amount = |cmd: &str| Command::from_str(cmd).unwrap().get_value().to_vec();
+
+    assert_eq!(amount("INCR key1"), b"1".to_vec());
+    assert_eq!(amount("decr key1"), b"-1".to_vec());
+    assert_eq!(amount("INCRBY key1 -5"), b"-5".to_vec());
+    assert_eq!(amount("DECRBY key1 5"), b"-5".to_vec());
+    assert_eq!(
+        Command::from_str("INCRBY key1 5").unwrap().get_name(),
+        &Type::IncrBy
+    );
+    assert!(Command::from_str("INCR").is_err());
+    assert!(Command::from_str("INCR key1 5").is_err());
+    assert!(Command::from_str("INCRBY key1").is_err());
+    assert!(Command::from_str("INCRBY key1 five").is_err());
+    assert!(Command::from_str("DECRBY key1 -9223372036854775808").is_err());
+}
+
+#[test]
+fn test_range_value_command() {
+    let cmd = Command::from_str("GETRANGE key1 0 -1").unwrap();
+
+    assert_eq!(cmd.get_name(), &Type::GetRange);
+    assert_eq!(cmd.get_args(), &vec![b"0".to_vec(), b"-1".to_vec()]);
+    assert_eq!(
+        Command::from_str("APPEND key1 \"a b\"")
+            .unwrap()
+            .get_value(),
+        &b"a b".to_vec()
+    );
+    assert_eq!(
+        Command::from_str("strlen key1").unwrap().get_name(),
+        &Type::StrLen
+    );
+    assert!(Command::from_str("APPEND key1 a 60").is_err());
+    assert!(Command::from_str("STRLEN key1 key2").is_err());
+    assert!(Command::from_str("GETRANGE key1 0").is_err());
+    assert!(Command::from_str("GETRANGE key1 0 end").is_err());
+}
+
+#[test]
+fn test_expire_command() {
+    let mut cmd: Command = Command::new("", "", 0, Type::Unknown);
+
+    // Test `TTL $key` command
+    if let Ok(v) = Command::from_str("TTL item1") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"item1".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Ttl);
+
+    // Test `EXPIRE $key $secs` command
+    if let Ok(v) = Command::from_str("EXPIRE item2 60") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"item2".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
+    assert_eq!(*cmd.get_expire(), 60);
+    assert_eq!(*cmd.get_name(), Type::Expire);
+
+    // Test `PERSIST $key` command
+    if let Ok(v) = Command::from_str("PERSIST item3") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_key(), b"item3".to_vec());
+    assert_eq!(*cmd.get_name(), Type::Persist);
+
+    // Test `STATS` command
+    if let Ok(v) = Command::from_str("STATS") {
+        cmd = v;
+    }
+
+    assert_eq!(*cmd.get_name(), Type::Stats);
+
+    assert!(Command::from_str("EXPIRE item2").is_err());
+    assert!(Command::from_str("EXPIRE item2 soon").is_err());
+    assert!(Command::from_str("SET item2 value2 -1").is_err());
+    assert!(Command::from_str("TTL").is_err());
+}
+
 #[test]
 fn test_error1_command() {
     let mut err: String = String::from("");
 
-    match Command::from_str("inver") {
-        Ok(_) => {}
-        Err(e) => err = e,
+    if let Err(e) = Command::from_str("inver") {
+        err = e.to_string();
     }
 
     assert_eq!(err, "Invalid command name `inver`".to_string());
@@ -275,9 +1026,8 @@ fn test_error2_command() {
     let mut err: String = String::from("");
 
     // Test `EXIT` command
-    match Command::from_str("get ") {
-        Ok(_) => {}
-        Err(e) => err = e,
+    if let Err(e) = Command::from_str("get ") {
+        err = e.to_string();
     }
 
     assert_eq!(err, "Invalid command get ".to_string());
@@ -288,10 +1038,73 @@ fn test_error3_command() {
     let mut err: String = String::from("");
 
     // Test `EXIT` command
-    match Command::from_str("update gs") {
-        Ok(_) => {}
-        Err(e) => err = e,
+    if let Err(e) = Command::from_str("update gs") {
+        err = e.to_string();
     }
 
     assert_eq!(err, "Invalid command update gs".to_string());
 }
+
+#[test]
+fn test_quoted_command() {
+    let cmd =
+        Command::from_str("SET \"my key\" \"line1\\nline2 \\\"q\\\" \\x00\\xff\" 10")
+            .unwrap();
+
+    assert_eq!(*cmd.get_key(), b"my key".to_vec());
+    assert_eq!(*cmd.get_value(), b"line1\nline2 \"q\" \x00\xff".to_vec());
+    assert_eq!(*cmd.get_expire(), 10);
+    assert_eq!(*cmd.get_name(), Type::Set);
+
+    let cmd = Command::from_bytes(b"SET key\xfe \"\"\r\n").unwrap();
+
+    assert_eq!(*cmd.get_key(), b"key\xfe".to_vec());
+    assert_eq!(*cmd.get_value(), b"".to_vec());
+
+    let cmd = Command::from_args(vec![b"SET".to_vec(), b"a b".to_vec(), b"\n".to_vec()]);
+
+    assert_eq!(*cmd.as_ref().unwrap().get_key(), b"a b".to_vec());
+    assert_eq!(*cmd.as_ref().unwrap().get_value(), b"\n".to_vec());
+    assert_eq!(
+        Command::from_args(vec![b"get".to_vec(), b"a b".to_vec()])
+            .unwrap()
+            .get_name(),
+        &Type::Get
+    );
+    assert_eq!(
+        Command::from_args(vec![b"SET".to_vec(), b"a b".to_vec()]).unwrap_err(),
+        Error::Protocol("Invalid command SET \"a b\"".to_string())
+    );
+
+    assert!(Command::from_str("SET key \"value").is_err());
+    assert!(Command::from_str("SET key \"value\"x").is_err());
+    assert!(Command::from_str("SET key \"\\xzz\"").is_err());
+}
+
+#[test]
+fn test_quote() {
+    let values: Vec<&[u8]> = vec![
+        b"value1",
+        b"",
+        b"hello world",
+        b"line1\nline2\t\"q\" \\",
+        b"\x00\xff\xfe",
+        "h\u{e9}llo \u{85}".as_bytes(),
+    ];
+
+    assert_eq!(quote(b"value1"), "value1".to_string());
+    assert_eq!(quote(b""), "\"\"".to_string());
+    assert_eq!(quote(b"a b"), "\"a b\"".to_string());
+    assert_eq!(quote(b"\x00\xff"), "\"\\x00\\xff\"".to_string());
+    assert_eq!(quote("h\u{e9}llo".as_bytes()), "h\u{e9}llo".to_string());
+    assert_eq!(quoted(b"value1"), "\"value1\"".to_string());
+
+    for value in values.into_iter() {
+        let line = format!("SET key {}", quote(value));
+
+        assert_eq!(
+            *Command::from_str(line).unwrap().get_value(),
+            value.to_vec()
+        );
+    }
+}
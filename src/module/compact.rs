@@ -1,3 +1,530 @@
 // Copyright 2022 Clivern. All rights reserved.
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
+
+use crate::module::database::Database;
+use crate::module::entry::{now, LogEntry};
+use crate::module::error::Error;
+use crate::module::hint::{Hint, HintEntry};
+use crate::module::keydir::{KeyDir, KeyDirEntry};
+use crate::module::reader::Reader;
+use crate::util::lock::{read_lock, write_lock};
+
+use std::fs::{create_dir_all, remove_dir_all, remove_file, rename, File, OpenOptions};
+use std::io::Write;
+use std::sync::RwLock;
+
+/// The directory inside the database directory the merged datafiles are
+/// written to before they are moved in
+pub const MERGE_DIR: &str = "merge";
+
+// Plan type
+pub struct Plan {
+    // The sealed datafile ids to merge in ascending order
+    ids: Vec<u32>,
+    // The id of the first merged datafile
+    first_id: u32,
+    // The last id reserved for the merged datafiles
+    last_id: u32,
+    // The highest entry version seen when the compaction started
+    version: u64,
+}
+
+// Plan type methods
+impl Plan {
+    ///
+    /// Returns the plan of a compaction
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The sealed datafile ids in ascending order
+    /// * `first_id` - The id of the first merged datafile
+    /// * `last_id` - The last id reserved for the merged datafiles
+    /// * `version` - The highest entry version seen
+    ///
+    pub fn new(ids: Vec<u32>, first_id: u32, last_id: u32, version: u64) -> Plan {
+        Plan {
+            ids,
+            first_id,
+            last_id,
+            version,
+        }
+    }
+
+    ///
+    /// Returns the sealed datafiles the plan merges
+    ///
+    /// # Returns
+    ///
+    /// * The ids of the sealed datafiles, in ascending order
+    ///
+    pub fn get_ids(&self) -> &Vec<u32> {
+        &self.ids
+    }
+}
+
+#[derive(Debug, Default)]
+// Merged type
+pub struct Merged {
+    // The merged datafile ids
+    ids: Vec<u32>,
+    // The moved keys with their old and new location
+    moved: Vec<(Vec<u8>, KeyDirEntry, KeyDirEntry)>,
+    // The expired keys with their old location
+    expired: Vec<(Vec<u8>, KeyDirEntry)>,
+}
+
+// Compact type
+pub struct Compact {
+    // The database directory holding the datafiles
+    path: String,
+    // The size threshold of the merged datafiles
+    max_file_size: u64,
+}
+
+// Compact type methods
+impl Compact {
+    ///
+    /// Returns a compact object for the database directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the database directory
+    /// * `max_file_size` - The size threshold of the merged datafiles
+    ///
+    pub fn new<S: Into<String>>(path: S, max_file_size: u64) -> Compact {
+        Compact {
+            path: path.into(),
+            max_file_size,
+        }
+    }
+
+    ///
+    /// Compacts a shared database
+    ///
+    /// The write lock is held to seal the active datafile and to swap the
+    /// merged datafiles in, reads and writes go on while merging. The file
+    /// I/O blocks, so async code runs it on a blocking thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    ///
+    /// # Returns
+    ///
+    /// * The number of merged datafiles
+    /// * Error raised
+    ///
+    pub fn run(db: &RwLock<Database>) -> Result<usize, Error> {
+        match Compact::start(db)? {
+            Some(plan) => Compact::finish(db, plan),
+            None => Ok(0),
+        }
+    }
+
+    ///
+    /// Seals the active datafile of a shared database to compact it
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    ///
+    /// # Returns
+    ///
+    /// * The compaction plan or None if no datafile is sealed
+    /// * Error raised if a compaction is already running
+    ///
+    pub fn start(db: &RwLock<Database>) -> Result<Option<Plan>, Error> {
+        write_lock(db).prepare_compaction()
+    }
+
+    ///
+    /// Merges the datafiles of a started compaction and swaps them in
+    ///
+    /// The keydir is checked under a short read lock for every entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    /// * `plan` - The plan returned when the compaction started
+    ///
+    /// # Returns
+    ///
+    /// * The number of merged datafiles
+    /// * Error raised
+    ///
+    pub fn finish(db: &RwLock<Database>, plan: Plan) -> Result<usize, Error> {
+        let compact = {
+            let db = read_lock(db);
+            Compact::new(db.get_path(), db.get_max_file_size())
+        };
+
+        let merged = compact.merge(&plan, |key, file_id, value_pos| {
+            read_lock(db).is_located_at(key, file_id, value_pos)
+        });
+
+        write_lock(db).complete_compaction(plan, merged)
+    }
+
+    ///
+    /// Merges sealed datafiles into new datafiles holding only live entries
+    ///
+    /// An entry is live when the keydir still points to it. The merged
+    /// datafiles and their hint files are written and synced in the merge
+    /// directory, the sealed datafiles and the keydir are left untouched.
+    /// Tombstones are dropped since every older datafile is merged too and
+    /// expired entries are dropped along with their keydir location. When
+    /// the highest version seen belongs to a dropped entry, a tombstone
+    /// carrying it is kept so bootup never hands that version out again.
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - The datafiles to merge
+    /// * `is_live` - Whether the keydir points a key at a datafile position
+    ///
+    /// # Returns
+    ///
+    /// * The merged datafiles and the keys to point at them
+    /// * Error raised
+    ///
+    pub fn merge<F>(&self, plan: &Plan, is_live: F) -> Result<Merged, Error>
+    where
+        F: Fn(&[u8], u32, u64) -> bool,
+    {
+        let dir = self.merge_path();
+        let _ = remove_dir_all(dir.as_str());
+
+        create_dir_all(dir.as_str())?;
+
+        let mut output = Output::new(dir.as_str(), plan.first_id);
+        let mut merged: Merged = Merged::default();
+        let mut dropped: Option<LogEntry> = None;
+        let now = now();
+
+        for id in plan.ids.iter() {
+            for item in Reader::open(self.datafile_path(*id))? {
+                let (offset, entry) = match item {
+                    Ok(item) => item,
+                    Err(_) => break,
+                };
+
+                let key = entry.get_key().to_vec();
+                let location = KeyDirEntry::new(
+                    *id,
+                    entry.get_value_size(),
+                    offset + entry.value_offset(),
+                    entry.get_timestamp(),
+                    entry.get_expire(),
+                    entry.get_version(),
+                );
+                let live = !entry.is_tombstone()
+                    && is_live(&key, *id, location.get_value_pos());
+
+                if !live || entry.is_expired(now) {
+                    if live {
+                        merged.expired.push((key, location));
+                    }
+
+                    if dropped
+                        .as_ref()
+                        .is_none_or(|last| last.get_version() < entry.get_version())
+                    {
+                        dropped = Some(entry);
+                    }
+
+                    continue;
+                }
+
+                if output.size > 0 && output.size + entry.size() > self.max_file_size {
+                    output.seal(&mut merged.ids)?;
+
+                    if output.id > plan.last_id {
+                        return Err(Error::State(
+                            "The merged datafiles exceed their reserved ids".to_string(),
+                        ));
+                    }
+                }
+
+                let position = output.write(&entry.encode())?;
+                let value_pos = position + entry.value_offset();
+
+                output.hints.push(HintEntry::new(
+                    entry.get_timestamp(),
+                    entry.get_expire(),
+                    entry.get_version(),
+                    entry.get_value_size(),
+                    value_pos,
+                    entry.get_key().to_vec(),
+                ));
+
+                merged.moved.push((
+                    key,
+                    location,
+                    KeyDirEntry::new(
+                        output.id,
+                        entry.get_value_size(),
+                        value_pos,
+                        entry.get_timestamp(),
+                        entry.get_expire(),
+                        entry.get_version(),
+                    ),
+                ));
+            }
+        }
+
+        if let Some(entry) = dropped {
+            if entry.get_version() > 0 && entry.get_version() == plan.version {
+                let mut tombstone = LogEntry::tombstone(entry.get_key().as_slice());
+
+                tombstone.set_version(entry.get_version());
+
+                let position = output.write(&tombstone.encode())?;
+
+                output.hints.push(HintEntry::new(
+                    tombstone.get_timestamp(),
+                    0,
+                    tombstone.get_version(),
+                    tombstone.get_value_size(),
+                    position + tombstone.value_offset(),
+                    tombstone.get_key().to_vec(),
+                ));
+            }
+        }
+
+        output.seal(&mut merged.ids)?;
+
+        Ok(merged)
+    }
+
+    ///
+    /// Moves the merged datafiles in and deletes the sealed ones
+    ///
+    /// A key is pointed at its merged entry, or removed if it expired,
+    /// only if it was not written since the plan was made.
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - The plan the datafiles were merged with
+    /// * `merged` - The merged datafiles
+    /// * `keydir` - The keydir of the database
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn install(
+        &self,
+        plan: &Plan,
+        merged: Merged,
+        keydir: &mut KeyDir,
+    ) -> Result<(), Error> {
+        let dir = self.merge_path();
+
+        for id in merged.ids.iter() {
+            rename(format!("{}/{}.hint", dir, id), self.hintfile_path(*id))?;
+            rename(format!("{}/{}.data", dir, id), self.datafile_path(*id))?;
+        }
+
+        for (key, old, new) in merged.moved.into_iter() {
+            if is_located_at(keydir, &key, &old) {
+                keydir.put(key, new);
+            }
+        }
+
+        for (key, old) in merged.expired.iter() {
+            if is_located_at(keydir, key, old) {
+                keydir.remove(key);
+            }
+        }
+
+        for id in plan.ids.iter() {
+            let _ = remove_file(self.hintfile_path(*id));
+            remove_file(self.datafile_path(*id))?;
+        }
+
+        let _ = remove_dir_all(dir.as_str());
+
+        Ok(())
+    }
+
+    ///
+    /// Removes the datafiles of a failed merge
+    ///
+    pub fn abort(&self) {
+        let _ = remove_dir_all(self.merge_path());
+    }
+
+    // Gets the path of a datafile
+    fn datafile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.data", self.path, file_id)
+    }
+
+    // Gets the path of a hint file
+    fn hintfile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.hint", self.path, file_id)
+    }
+
+    // Gets the path of the merge directory
+    fn merge_path(&self) -> String {
+        format!("{}/{}", self.path, MERGE_DIR)
+    }
+}
+
+// Whether the keydir still points a key at the datafile position of a location
+fn is_located_at(keydir: &KeyDir, key: &[u8], location: &KeyDirEntry) -> bool {
+    keydir.get(key).is_some_and(|current| {
+        current.get_file_id() == location.get_file_id()
+            && current.get_value_pos() == location.get_value_pos()
+    })
+}
+
+// The merged datafile being written
+struct Output {
+    // The database directory
+    path: String,
+    // The merged datafile id
+    id: u32,
+    // The merged datafile, opened on the first write
+    file: Option<File>,
+    // The merged datafile size in bytes
+    size: u64,
+    // The hint entries of the merged datafile
+    hints: Vec<HintEntry>,
+}
+
+impl Output {
+    fn new(path: &str, id: u32) -> Output {
+        Output {
+            path: path.to_string(),
+            id,
+            file: None,
+            size: 0,
+            hints: Vec::new(),
+        }
+    }
+
+    // Writes the bytes and returns their offset
+    fn write(&mut self, buf: &[u8]) -> Result<u64, Error> {
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(format!("{}/{}.data", self.path, self.id))?;
+
+            self.file = Some(file);
+        }
+
+        let offset = self.size;
+
+        if let Some(f) = self.file.as_mut() {
+            f.write_all(buf)?;
+        }
+
+        self.size += buf.len() as u64;
+
+        Ok(offset)
+    }
+
+    // Syncs the merged datafile, writes its hint file and moves to the next id
+    fn seal(&mut self, ids: &mut Vec<u32>) -> Result<(), Error> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+
+        file.sync_all()?;
+
+        Hint::new(format!("{}/{}.hint", self.path, self.id)).write(&self.hints)?;
+
+        ids.push(self.id);
+
+        self.id += 1;
+        self.size = 0;
+        self.hints.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::bootup::Bootup;
+    use crate::module::entry::LogEntry;
+    use crate::module::writer::Writer;
+
+    #[test]
+    /// test merge method
+    fn test_merge() {
+        let _ = std::fs::remove_dir_all("cache/compact_merge");
+        let _ = std::fs::create_dir_all("cache/compact_merge");
+
+        let wt: Writer = Writer::new();
+        let first = "cache/compact_merge/1.data".to_string();
+        let second = "cache/compact_merge/2.data".to_string();
+
+        let _ = wt.append_entry(first.to_string(), &LogEntry::new("key1", "value1"));
+        let _ = wt.append_entry(first.to_string(), &LogEntry::new("key2", "value2"));
+        let _ = wt.append_entry(first.to_string(), &LogEntry::new("key3", "value3"));
+        let _ = wt.append_entry(second.to_string(), &LogEntry::new("key1", "value4"));
+        let _ = wt.append_entry(second.to_string(), &LogEntry::tombstone("key2"));
+        let _ = wt.append_entry(
+            second.to_string(),
+            &LogEntry::with_expire("key4", "value5", 1652000000),
+        );
+
+        let mut keydir: KeyDir = KeyDir::new();
+        let _ = Bootup::new("cache/compact_merge").rebuild(&mut keydir);
+
+        // Bootup drops key4, point at it as if it expired after bootup
+        keydir.put(
+            "key4",
+            KeyDirEntry::new(2, 6, 46 + 40 + 36 + 4, 1652000000, 1652000000, 0),
+        );
+
+        // Two live entries of 46 bytes fit in one merged datafile
+        let compact: Compact = Compact::new("cache/compact_merge", 1024);
+
+        let plan: Plan = Plan::new(vec![1, 2], 4, 5, keydir.get_version());
+        let merged = compact
+            .merge(&plan, |key, file_id, value_pos| {
+                keydir.get(key).is_some_and(|location| {
+                    location.get_file_id() == file_id
+                        && location.get_value_pos() == value_pos
+                })
+            })
+            .unwrap();
+
+        assert_eq!(merged.ids, vec![4]);
+        assert!(wt.file_exists(first.to_string()));
+        assert!(wt.file_exists("cache/compact_merge/merge/4.data".to_string()));
+
+        // A key written during the merge keeps its new location
+        keydir.put("key3", KeyDirEntry::new(6, 6, 32, 1652000000, 0, 7));
+
+        assert_eq!(compact.install(&plan, merged, &mut keydir), Ok(()));
+        assert!(!wt.file_exists(first));
+        assert!(!wt.file_exists(second));
+        assert!(!wt.file_exists("cache/compact_merge/1.hint".to_string()));
+        assert_eq!(
+            wt.filesize("cache/compact_merge/4.data".to_string()),
+            Ok(92)
+        );
+
+        assert_eq!(keydir.len(), 2);
+        assert!(!keydir.contains("key4"));
+        assert_eq!(keydir.get("key3").unwrap().get_file_id(), 6);
+        assert_eq!(keydir.get("key1").unwrap().get_file_id(), 4);
+        assert!(!wt.file_exists("cache/compact_merge/merge/4.data".to_string()));
+
+        let mut rebuilt: KeyDir = KeyDir::new();
+
+        assert_eq!(
+            Bootup::new("cache/compact_merge").rebuild(&mut rebuilt),
+            Ok(4)
+        );
+        assert_eq!(rebuilt.get("key1"), keydir.get("key1"));
+        assert_eq!(rebuilt.get("key3").unwrap().get_file_id(), 4);
+        assert!(!rebuilt.contains("key2"));
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::codec::{Codec, Request};
+use crate::module::command::{Command, Type};
+use crate::module::error::Error;
+use crate::module::handler::Session;
+use crate::module::namespace::{Namespaces, DEFAULT_NAMESPACE};
+use crate::module::resp::{Resp, Value};
+use crate::module::response::{Code, Response};
+
+/// The reply bytes gathered before they are written to the socket
+pub const FLUSH_SIZE: usize = 65536;
+
+#[derive(Debug)]
+// Connection type
+pub struct Connection {
+    // Splits the received bytes into requests
+    codec: Codec,
+    // The plain text protocol state
+    session: Session,
+    // The RESP protocol state
+    resp: Resp,
+    // The namespace selected by SELECT
+    namespace: String,
+    // Whether the connection must be closed once the replies are written
+    closed: bool,
+}
+
+// Connection type methods
+impl Connection {
+    ///
+    /// Returns the state of a new client connection
+    ///
+    /// # Arguments
+    ///
+    /// * `max_command_size` - The maximum size of a command in bytes
+    ///
+    pub fn new(max_command_size: usize) -> Connection {
+        Connection {
+            codec: Codec::new(max_command_size),
+            session: Session::new(),
+            resp: Resp::new(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            closed: false,
+        }
+    }
+
+    ///
+    /// Whether the connection must be closed once the replies are written
+    ///
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    ///
+    /// Returns the namespace selected by the connection
+    ///
+    /// # Returns
+    ///
+    /// * The namespace name, `0` until `SELECT` picks another one
+    ///
+    pub fn get_namespace(&self) -> String {
+        self.namespace.to_string()
+    }
+
+    ///
+    /// Appends bytes read from the socket
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The received bytes
+    ///
+    pub fn feed(&mut self, data: &[u8]) {
+        self.codec.feed(data);
+    }
+
+    ///
+    /// Executes the buffered requests in order and gathers their replies
+    ///
+    /// It stops once `FLUSH_SIZE` reply bytes are gathered so a long
+    /// pipeline is written back in batches, the caller writes them and
+    /// calls it again until it replies nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `namespaces` - The namespaces shared by all connections
+    ///
+    /// # Returns
+    ///
+    /// * The replies to write back to the client
+    ///
+    pub fn pipeline(&mut self, namespaces: &Namespaces) -> Vec<u8> {
+        let mut out: Vec<u8> = Vec::new();
+
+        while !self.closed && out.len() < FLUSH_SIZE {
+            match self.codec.decode() {
+                Ok(Some(request)) => out.extend(self.execute(namespaces, request)),
+                Ok(None) => break,
+                // The frames that follow can not be found again
+                Err(e) if self.codec.is_resp() => {
+                    out.extend(
+                        Value::Error(format!("ERR {}", e))
+                            .encode(self.resp.get_version()),
+                    );
+                    self.closed = true;
+                }
+                Err(e) => {
+                    out.extend(Response::error(Code::TooLarge, e.to_string()).encode())
+                }
+            }
+        }
+
+        out
+    }
+
+    ///
+    /// Executes the unterminated command left when the client stops sending
+    ///
+    /// # Arguments
+    ///
+    /// * `namespaces` - The namespaces shared by all connections
+    ///
+    /// # Returns
+    ///
+    /// * The reply to write back to the client
+    ///
+    pub fn finish(&mut self, namespaces: &Namespaces) -> Vec<u8> {
+        let out = match self.codec.finish() {
+            Some(request) => self.execute(namespaces, request),
+            None => Vec::new(),
+        };
+
+        self.closed = true;
+        out
+    }
+
+    // Executes a request and returns the reply bytes
+    fn execute(&mut self, namespaces: &Namespaces, request: Request) -> Vec<u8> {
+        let line = match request {
+            Request::Args(args) => return self.execute_resp(namespaces, args),
+            Request::Line(line) => line,
+        };
+
+        let cmd = match Command::from_bytes(&line) {
+            Ok(cmd) => cmd,
+            Err(e) => return Response::from(e).encode(),
+        };
+
+        let out = match cmd.get_name() {
+            Type::Select if self.session.in_transaction() => {
+                Response::error(Code::State, "SELECT is not allowed in a transaction")
+            }
+            Type::Select => match self.select(namespaces, cmd.get_key()) {
+                Ok(_) => Response::ok(),
+                Err(e) => Response::from(e),
+            },
+            _ => match namespaces.open(self.namespace.as_str()) {
+                Ok(db) => self.session.handle(&db, &cmd),
+                Err(e) => Response::from(e),
+            },
+        };
+
+        self.closed = *cmd.get_name() == Type::Exit;
+
+        out.encode()
+    }
+
+    // Executes a RESP request and returns the reply bytes
+    fn execute_resp(&mut self, namespaces: &Namespaces, args: Vec<Vec<u8>>) -> Vec<u8> {
+        let name = args.first().cloned().unwrap_or_default();
+
+        self.closed = name.eq_ignore_ascii_case(b"QUIT");
+
+        let reply = match (name.eq_ignore_ascii_case(b"SELECT"), args.len()) {
+            (true, 2) => match self.select(namespaces, &args[1]) {
+                Ok(_) => Value::Simple("OK".to_string()),
+                Err(e) => Value::Error(format!("ERR {}", e)),
+            },
+            (true, _) => Value::Error(
+                "ERR wrong number of arguments for 'select' command".to_string(),
+            ),
+            _ => match namespaces.open(self.namespace.as_str()) {
+                Ok(db) => return self.resp.handle(&db, args),
+                Err(e) => Value::Error(format!("ERR {}", e)),
+            },
+        };
+
+        reply.encode(self.resp.get_version())
+    }
+
+    // Switches the connection to a namespace, loading it on first use
+    fn select(&mut self, namespaces: &Namespaces, name: &[u8]) -> Result<(), Error> {
+        let name = String::from_utf8_lossy(name).to_string();
+
+        namespaces.open(name.as_str())?;
+        self.namespace = name;
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_pipeline() {
+    let _ = std::fs::remove_dir_all("./cache/connection_pipeline");
+
+    let db = Namespaces::new("./cache/connection_pipeline");
+    let mut conn: Connection = Connection::new(64);
+
+    conn.feed(b"SET key1 value1\nGET key1\nDELETE key1\nGET key1\nGET");
+
+    assert_eq!(conn.pipeline(&db), b"OK\n\"value1\"\nOK\nNIL\n".to_vec());
+    assert_eq!(conn.pipeline(&db), Vec::<u8>::new());
+
+    conn.feed(b" key1\n");
+
+    assert_eq!(conn.pipeline(&db), b"NIL\n".to_vec());
+
+    conn.feed(
+        b"GET\nSET key1 \
+        a-value-larger-than-the-maximum-command-size-of-the-connection\n",
+    );
+
+    assert_eq!(
+        conn.pipeline(&db),
+        b"ERR_SYNTAX Invalid command GET\n\
+        ERR_TOO_LARGE Command exceeds the maximum size of 64 bytes\n"
+            .to_vec()
+    );
+
+    // A long pipeline is replied in batches
+    let mut requests: Vec<u8> = Vec::new();
+
+    for _ in 0..20000 {
+        requests.extend_from_slice(b"PING\n");
+    }
+
+    conn.feed(&requests);
+
+    let first = conn.pipeline(&db);
+
+    assert!(first.len() >= FLUSH_SIZE && first.len() < 20000 * 5);
+    assert_eq!(first.len() + conn.pipeline(&db).len(), 20000 * 5);
+    assert_eq!(conn.pipeline(&db), Vec::<u8>::new());
+
+    conn.feed(b"PING");
+
+    assert!(!conn.is_closed());
+    assert_eq!(conn.finish(&db), b"PONG\n".to_vec());
+    assert!(conn.is_closed());
+}
+
+#[test]
+fn test_pipeline_exit() {
+    let _ = std::fs::remove_dir_all("./cache/connection_exit");
+
+    let db = Namespaces::new("./cache/connection_exit");
+    let mut conn: Connection = Connection::new(64);
+
+    // The commands sent after EXIT are dropped
+    conn.feed(b"PING\nEXIT\nSET key1 value1\n");
+
+    assert_eq!(conn.pipeline(&db), b"PONG\nOK\n".to_vec());
+    assert!(conn.is_closed());
+    assert_eq!(
+        db.open(DEFAULT_NAMESPACE)
+            .unwrap()
+            .read()
+            .unwrap()
+            .get("key1"),
+        Ok(None)
+    );
+
+    let mut conn: Connection = Connection::new(64);
+
+    conn.feed(b"*1\r\n$4\r\nquit\r\n*1\r\n$4\r\nPING\r\n");
+
+    assert_eq!(conn.pipeline(&db), b"+OK\r\n".to_vec());
+    assert!(conn.is_closed());
+}
+
+#[test]
+fn test_pipeline_resp() {
+    let _ = std::fs::remove_dir_all("./cache/connection_resp");
+
+    let db = Namespaces::new("./cache/connection_resp");
+    let mut conn: Connection = Connection::new(64);
+
+    conn.feed(
+        b"*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n*2\r\n$3\r\nGET\r\n$1\r\na\r\n",
+    );
+
+    assert_eq!(conn.pipeline(&db), b"+OK\r\n$1\r\n1\r\n".to_vec());
+
+    conn.feed(b"*1\r\n$4\r\nPING\r\n+bad\r\n*1\r\n$4\r\nPING\r\n");
+
+    assert_eq!(
+        conn.pipeline(&db),
+        b"+PONG\r\n-ERR Protocol error: expected '*', got '+'\r\n".to_vec()
+    );
+    assert!(conn.is_closed());
+}
+
+#[test]
+fn test_pipeline_select() {
+    let _ = std::fs::remove_dir_all("./cache/connection_select");
+
+    let db = Namespaces::new("./cache/connection_select");
+    let mut conn: Connection = Connection::new(64);
+
+    conn.feed(b"SET key1 value1\nSELECT orders\nGET key1\nSET key1 value2\n");
+
+    assert_eq!(conn.pipeline(&db), b"OK\nOK\nNIL\nOK\n".to_vec());
+    assert_eq!(conn.get_namespace(), "orders".to_string());
+
+    conn.feed(b"SELECT ../orders\nMULTI\nSELECT 0\nDISCARD\nSELECT 0\nGET key1\n");
+
+    assert_eq!(
+        conn.pipeline(&db),
+        b"ERR_SYNTAX Invalid namespace `../orders`\nOK\n\
+        ERR_STATE SELECT is not allowed in a transaction\nOK\nOK\n\"value1\"\n"
+            .to_vec()
+    );
+
+    let mut conn: Connection = Connection::new(64);
+
+    conn.feed(
+        b"*2\r\n$6\r\nSELECT\r\n$6\r\norders\r\n*2\r\n$3\r\nGET\r\n$4\r\nkey1\r\n",
+    );
+
+    assert_eq!(conn.pipeline(&db), b"+OK\r\n$6\r\nvalue2\r\n".to_vec());
+
+    conn.feed(b"*1\r\n$6\r\nSELECT\r\n");
+
+    assert_eq!(
+        conn.pipeline(&db),
+        b"-ERR wrong number of arguments for 'select' command\r\n".to_vec()
+    );
+    assert_eq!(db.loaded().len(), 2);
+}
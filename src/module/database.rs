@@ -2,25 +2,75 @@
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
 
-use bincode;
-use bincode::serialize_into;
-use serde::{Deserialize, Serialize};
+use crate::module::bootup::Bootup;
+use crate::module::compact::{Compact, Merged, Plan};
+use crate::module::entry::{
+    encode_batch, expire_at, now, LogEntry, HEADER_SIZE, TOMBSTONE,
+};
+use crate::module::error::Error;
+use crate::module::hint::Hint;
+use crate::module::keydir::{IndexType, KeyDir, KeyDirEntry};
+use crate::module::writer::Writer;
+use crate::util::pattern::matches;
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::sync::RwLock;
+use std::fs::{create_dir_all, remove_file, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
 
-#[derive(Serialize, Debug, Deserialize)]
+#[derive(Debug)]
 // Database type
-struct Database {
-    // the sync lock
-    lock: RwLock<()>,
-    // The database path
+pub struct Database {
+    // The database directory holding the datafiles
     path: String,
-    // The k/v data
-    data: HashMap<String, String>,
+    // The in-memory index of value locations
+    keydir: KeyDir,
+    // The active datafile id
+    active_id: u32,
+    // The active datafile, opened on the first write
+    active: Option<File>,
+    // The active datafile size in bytes
+    active_size: u64,
+    // The size threshold at which the active datafile is rotated
+    max_file_size: u64,
+    // The size of all datafiles in bytes
+    total_bytes: u64,
+    // The size of overwritten, deleted and tombstone entries in bytes
+    dead_bytes: u64,
+    // The dead bytes ratio that triggers a compaction
+    compact_ratio: f64,
+    // The number of expired keys removed by the sweeper
+    expired_keys: u64,
+    // Whether a compaction is merging the sealed datafiles
+    compacting: bool,
+    // The size `APPEND` can grow a value to
+    max_value_size: usize,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+// Operation type
+pub enum Operation {
+    // Stores or updates a key value expiring at a unix timestamp or zero
+    Set(Vec<u8>, Vec<u8>, u64),
+    // Removes a key
+    Delete(Vec<u8>),
+}
+
+/// A key and its value
+pub type Pair = (Vec<u8>, Vec<u8>);
+
+/// The default size threshold of the active datafile in bytes
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 67108864;
+
+/// The default dead bytes ratio that triggers a compaction
+pub const DEFAULT_COMPACT_RATIO: f64 = 0.5;
+
+/// The default size `APPEND` can grow a value to, well below the tombstone marker
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 536870912;
+
+/// The file written in the database directory by a clean shutdown
+pub const CLEAN_SHUTDOWN_FILE: &str = "clean_shutdown";
+
 // Database type methods
 impl Database {
     ///
@@ -28,20 +78,145 @@ impl Database {
     ///
     /// # Arguments
     ///
-    /// * `path` - A string that holds the path to the database
+    /// * `path` - A string that holds the path to the database directory
     ///
     /// # Examples
     ///
     /// ```
-    /// let mut db: Database = Database::new("/etc/langmore/langmore.db");
+    /// use langmore::module::database::Database;
+    ///
+    /// let mut db: Database = Database::new("/etc/langmore");
     /// ```
     ///
     pub fn new<S: Into<String>>(path: S) -> Database {
         Database {
-            lock: RwLock::new(()),
             path: path.into(),
-            data: HashMap::new(),
+            keydir: KeyDir::new(),
+            active_id: 1,
+            active: None,
+            active_size: 0,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            total_bytes: 0,
+            dead_bytes: 0,
+            compact_ratio: DEFAULT_COMPACT_RATIO,
+            expired_keys: 0,
+            compacting: false,
+            max_value_size: DEFAULT_MAX_VALUE_SIZE,
+        }
+    }
+
+    ///
+    /// Updates the dead bytes ratio that triggers a compaction
+    ///
+    /// # Arguments
+    ///
+    /// * `ratio` - The ratio of dead bytes to all datafile bytes
+    ///
+    pub fn set_compact_ratio(&mut self, ratio: f64) {
+        self.compact_ratio = ratio;
+    }
+
+    ///
+    /// Gets the dead bytes ratio that triggers a compaction
+    ///
+    pub fn get_compact_ratio(&self) -> f64 {
+        self.compact_ratio
+    }
+
+    ///
+    /// Updates the index type of the keydir
+    ///
+    /// The keys already loaded are moved to the new index.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index type
+    ///
+    pub fn set_index_type(&mut self, index: IndexType) {
+        if self.keydir.get_index_type() == index {
+            return;
         }
+
+        let mut keydir = KeyDir::with_index(index);
+
+        for (key, location) in self.keydir.iter() {
+            keydir.put(key.to_vec(), location.clone());
+        }
+
+        keydir.bump_version(self.keydir.get_version());
+        self.keydir = keydir;
+    }
+
+    ///
+    /// Gets the index type of the keydir
+    ///
+    pub fn get_index_type(&self) -> IndexType {
+        self.keydir.get_index_type()
+    }
+
+    ///
+    /// Gets the size of all datafiles in bytes
+    ///
+    pub fn get_total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    ///
+    /// Gets the size of overwritten, deleted and tombstone entries in bytes
+    ///
+    pub fn get_dead_bytes(&self) -> u64 {
+        self.dead_bytes
+    }
+
+    ///
+    /// Gets the number of expired keys removed by the sweeper
+    ///
+    pub fn get_expired_keys(&self) -> u64 {
+        self.expired_keys
+    }
+
+    ///
+    /// Updates the size threshold of the active datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The maximum datafile size in bytes
+    ///
+    pub fn set_max_file_size(&mut self, size: u64) {
+        self.max_file_size = size;
+    }
+
+    ///
+    /// Gets the size threshold of the active datafile
+    ///
+    /// # Returns
+    ///
+    /// * The maximum datafile size in bytes
+    ///
+    pub fn get_max_file_size(&self) -> u64 {
+        self.max_file_size
+    }
+
+    ///
+    /// Updates the size `APPEND` can grow a value to
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The maximum value size in bytes, kept below the tombstone marker
+    ///
+    pub fn set_max_value_size(&mut self, size: usize) {
+        self.max_value_size = size.min(TOMBSTONE as usize - 1);
+    }
+
+    ///
+    /// Gets the active datafile id
+    ///
+    /// # Returns
+    ///
+    /// * The id of the datafile open for writing
+    ///
+    pub fn get_active_id(&self) -> u32 {
+        self.active_id
     }
 
     ///
@@ -49,11 +224,11 @@ impl Database {
     ///
     /// # Arguments
     ///
-    /// * `path` - A string that holds the path to the database
+    /// * `path` - A string that holds the path to the database directory
     ///
     pub fn set_path<S: Into<String>>(&mut self, path: S) {
-        let _lock = self.lock.write().expect("Lock is used");
         self.path = path.into();
+        self.active = None;
     }
 
     ///
@@ -61,10 +236,63 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// * `path` - A string that holds the path to the database
+    /// * `path` - A string that holds the path to the database directory
     ///
     pub fn get_path(&self) -> String {
-        format!("{}", self.path)
+        self.path.to_string()
+    }
+
+    ///
+    /// Gets the active datafile size
+    ///
+    /// # Returns
+    ///
+    /// * The bytes written to the active datafile
+    ///
+    pub fn get_active_size(&self) -> u64 {
+        self.active_size
+    }
+
+    ///
+    /// Lists the datafile ids in the database directory
+    ///
+    /// # Returns
+    ///
+    /// * The datafile ids in ascending order
+    /// * Error raised
+    ///
+    pub fn datafile_ids(&self) -> Result<Vec<u32>, Error> {
+        Bootup::new(self.path.as_str()).datafile_ids()
+    }
+
+    ///
+    /// Gets the path of a datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The datafile id
+    ///
+    /// # Returns
+    ///
+    /// * The datafile path inside the database directory
+    ///
+    pub fn datafile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.data", self.path, file_id)
+    }
+
+    ///
+    /// Gets the path of a hint file
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The datafile id
+    ///
+    /// # Returns
+    ///
+    /// * The hint file path inside the database directory
+    ///
+    pub fn hintfile_path(&self, file_id: u32) -> String {
+        format!("{}/{}.hint", self.path, file_id)
     }
 
     ///
@@ -72,109 +300,1582 @@ impl Database {
     ///
     /// # Arguments
     ///
-    /// * `key` - A string that holds the key
-    /// * `value` - A string that holds the value
+    /// * `key` - The key bytes
+    /// * `value` - The value bytes
     ///
     /// # Returns
     ///
     /// * The number of records in the database
+    /// * Error raised
     ///
-    pub fn set<S: Into<String>>(&mut self, key: S, value: S) -> usize {
-        let _lock = self.lock.write().expect("Lock is used");
-        self.data.insert(key.into(), value.into());
-        self.data.len()
+    pub fn set<S: Into<Vec<u8>>>(&mut self, key: S, value: S) -> Result<usize, Error> {
+        self.apply(vec![Operation::Set(key.into(), value.into(), 0)])
     }
 
     ///
-    /// Removes a Key from the database
+    /// Store or Update a Key Value that expires after a time to live
     ///
     /// # Arguments
     ///
-    /// * `key` - A string that holds the key
+    /// * `key` - The key bytes
+    /// * `value` - The value bytes
+    /// * `ttl` - The time to live in seconds or zero to never expire
     ///
     /// # Returns
     ///
     /// * The number of records in the database
+    /// * Error raised
     ///
-    pub fn remove<S: Into<String>>(&mut self, key: S) -> usize {
-        let _lock = self.lock.write().expect("Lock is used");
-        self.data.remove(&key.into());
-        self.data.len()
+    pub fn set_with_ttl<S: Into<Vec<u8>>>(
+        &mut self,
+        key: S,
+        value: S,
+        ttl: u64,
+    ) -> Result<usize, Error> {
+        self.apply(vec![Operation::Set(
+            key.into(),
+            value.into(),
+            expire_at(ttl),
+        )])
     }
 
     ///
-    /// Get the Value of a Key
+    /// Updates the time to live of a Key
+    ///
+    /// The value is written again with the new expiry, a zero time to live
+    /// removes the key right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `ttl` - The time to live in seconds
+    ///
+    /// # Returns
+    ///
+    /// * Whether the key exists
+    /// * Error raised
+    ///
+    pub fn expire<S: Into<Vec<u8>>>(&mut self, key: S, ttl: u64) -> Result<bool, Error> {
+        let key = key.into();
+
+        let value = match self.get(&key)? {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+
+        match ttl {
+            0 => self.remove(key)?,
+            ttl => self.apply(vec![Operation::Set(key, value, expire_at(ttl))])?,
+        };
+
+        Ok(true)
+    }
+
+    ///
+    /// Adds an amount to the integer Value of a Key
+    ///
+    /// A missing Key counts as zero and the time to live of the Key is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `amount` - The amount to add, negative to subtract
+    ///
+    /// # Returns
+    ///
+    /// * The new Value or None if the Value is not an integer or overflows
+    /// * Error raised
+    ///
+    pub fn increment<S: Into<Vec<u8>>>(
+        &mut self,
+        key: S,
+        amount: i64,
+    ) -> Result<Option<i64>, Error> {
+        let key = key.into();
+
+        let current = match self.get(&key)? {
+            Some(value) => match std::str::from_utf8(&value)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+            {
+                Some(current) => current,
+                None => return Ok(None),
+            },
+            None => 0,
+        };
+
+        let value = match current.checked_add(amount) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let expire = match self.keydir.get(&key) {
+            Some(location) if !location.is_expired(now()) => location.get_expire(),
+            _ => 0,
+        };
+
+        self.apply(vec![Operation::Set(
+            key,
+            value.to_string().into_bytes(),
+            expire,
+        )])?;
+
+        Ok(Some(value))
+    }
+
+    ///
+    /// Appends bytes to the Value of a Key
+    ///
+    /// A missing Key counts as an empty Value and the time to live of the
+    /// Key is kept. The whole Value is written again as a new entry since
+    /// the datafiles are append only, so growing a Value by small appends
+    /// costs its length in reads, writes and dead bytes every time. A Value
+    /// can not grow over the maximum value size.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `value` - The bytes to append
+    ///
+    /// # Returns
+    ///
+    /// * The length of the new Value
+    /// * Error raised
+    ///
+    pub fn append_value<S: Into<Vec<u8>>>(
+        &mut self,
+        key: S,
+        value: S,
+    ) -> Result<usize, Error> {
+        let key = key.into();
+        let value = value.into();
+
+        if self.strlen(&key).saturating_add(value.len()) > self.max_value_size {
+            return Err(Error::State(format!(
+                "The value can not grow over {} bytes",
+                self.max_value_size
+            )));
+        }
+
+        let mut current = self.get(&key)?.unwrap_or_default();
+
+        current.extend_from_slice(&value);
+
+        let expire = match self.keydir.get(&key) {
+            Some(location) if !location.is_expired(now()) => location.get_expire(),
+            _ => 0,
+        };
+        let len = current.len();
+
+        self.apply(vec![Operation::Set(key, current, expire)])?;
+
+        Ok(len)
+    }
+
+    ///
+    /// Gets the length of the Value of a Key from the KeyDir
     ///
     /// # Arguments
     ///
-    /// * `key` - A string that holds the key
+    /// * `key` - The key bytes
     ///
     /// # Returns
     ///
-    /// * The Value of the Key
+    /// * The length of the Value or zero if the Key is missing or expired
     ///
-    pub fn get<S: Into<String>>(&self, key: S) -> String {
-        match self.data.get(&key.into()) {
-            Some(value) => value.to_string(),
-            _ => String::new(),
+    pub fn strlen<K: AsRef<[u8]>>(&self, key: K) -> usize {
+        match self.keydir.get(key) {
+            Some(location) if !location.is_expired(now()) => {
+                location.get_value_sz() as usize
+            }
+            _ => 0,
         }
     }
 
     ///
-    /// Loads the database from the path and return Database object
+    /// Gets part of the Value of a Key, reading only the requested bytes
+    ///
+    /// A negative offset counts from the end of the Value and offsets past
+    /// either end are clamped to the Value.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `start` - The offset of the first byte
+    /// * `end` - The offset of the last byte, included
     ///
     /// # Returns
     ///
-    /// *  An instance of the database object
+    /// * The bytes, empty if the Key is missing or the range is empty
+    /// * Error raised
     ///
-    pub fn load(&self) -> Database {
-        let _lock = self.lock.write().expect("Lock is used");
-        let f = File::open(self.path.as_str()).unwrap();
-        let mut db: Database = Database::new(self.path.as_str());
-        db.data = bincode::deserialize_from(&f).unwrap();
-        db
+    pub fn get_range<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<u8>, Error> {
+        let location = match self.keydir.get(key) {
+            Some(location) if !location.is_expired(now()) => location,
+            _ => return Ok(Vec::new()),
+        };
+
+        let size = location.get_value_sz() as i64;
+        let offset = |pos: i64| if pos < 0 { (size + pos).max(0) } else { pos };
+        let (start, end) = (offset(start), offset(end).min(size - 1));
+
+        if start > end {
+            return Ok(Vec::new());
+        }
+
+        self.read_at(location, start as u64, (end - start + 1) as usize)
     }
 
     ///
-    /// Serialize the data and store them in the database path
+    /// Removes the time to live of a Key
     ///
-    pub fn flush(&self) {
-        let _lock = self.lock.write().expect("Lock is used");
-        let mut f = File::create(self.path.as_str()).unwrap();
-        serialize_into(&mut f, &self.data).unwrap();
-        drop(f)
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * Whether the key exists
+    /// * Error raised
+    ///
+    pub fn persist<S: Into<Vec<u8>>>(&mut self, key: S) -> Result<bool, Error> {
+        let key = key.into();
+
+        match self.ttl(&key) {
+            None => return Ok(false),
+            Some(-1) => return Ok(true),
+            Some(_) => {}
+        }
+
+        if let Some(value) = self.get(&key)? {
+            self.apply(vec![Operation::Set(key, value, 0)])?;
+        }
+
+        Ok(true)
     }
-}
 
-#[test]
-fn test_database_methods() {
-    let mut db: Database = Database::new("./cache/langmore.db");
+    ///
+    /// Gets the remaining time to live of a Key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The remaining seconds, -1 if the key never expires or None if
+    ///   the key does not exist
+    ///
+    pub fn ttl<K: AsRef<[u8]>>(&self, key: K) -> Option<i64> {
+        let now = now();
+
+        match self.keydir.get(key) {
+            Some(location) if location.is_expired(now) => None,
+            Some(location) if location.get_expire() == 0 => Some(-1),
+            Some(location) => Some((location.get_expire() - now) as i64),
+            None => None,
+        }
+    }
+
+    ///
+    /// Removes a Key from the database
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The number of records in the database
+    /// * Error raised
+    ///
+    pub fn remove<S: Into<Vec<u8>>>(&mut self, key: S) -> Result<usize, Error> {
+        self.apply(vec![Operation::Delete(key.into())])
+    }
+
+    ///
+    /// Applies write operations atomically
+    ///
+    /// A single operation is written as a plain entry while several ones
+    /// are written as one batch record, so either all of them or none are
+    /// recovered after a crash. Deleting a missing or expired key writes
+    /// nothing. Every entry is stamped with the next version of the database.
+    ///
+    /// # Arguments
+    ///
+    /// * `operations` - The write operations in order
+    ///
+    /// # Returns
+    ///
+    /// * The number of records in the database
+    /// * Error raised
+    ///
+    pub fn apply(&mut self, operations: Vec<Operation>) -> Result<usize, Error> {
+        let mut entries: Vec<LogEntry> = Vec::new();
+        let mut live: HashMap<Vec<u8>, bool> = HashMap::new();
+
+        for operation in operations.into_iter() {
+            match operation {
+                Operation::Set(key, value, expire) => {
+                    entries.push(LogEntry::with_expire(key.as_slice(), &value, expire));
+                    live.insert(key, true);
+                }
+                Operation::Delete(key) => {
+                    let exists = match live.get(&key) {
+                        Some(exists) => *exists,
+                        None => self.exists(&key),
+                    };
+
+                    if exists {
+                        entries.push(LogEntry::tombstone(key.as_slice()));
+                        live.insert(key, false);
+                    }
+                }
+            }
+        }
+
+        self.write(entries)
+    }
+
+    // Writes entries as a plain entry or a batch and updates the keydir
+    fn write(&mut self, mut entries: Vec<LogEntry>) -> Result<usize, Error> {
+        let mut version = self.keydir.get_version();
+
+        for entry in entries.iter_mut() {
+            version += 1;
+            entry.set_version(version);
+        }
 
-    db.set_path("./cache/langmore.db");
+        let offsets: Vec<u64> = match entries.len() {
+            0 => return Ok(self.keydir.len()),
+            1 => vec![self.append(&entries[0].encode())?],
+            _ => {
+                let mut offset =
+                    self.append(&encode_batch(&entries))? + HEADER_SIZE as u64;
+                self.dead_bytes += HEADER_SIZE as u64;
 
-    assert_eq!(db.get_path(), "./cache/langmore.db".to_string());
+                entries
+                    .iter()
+                    .map(|entry| {
+                        let position = offset;
+                        offset += entry.size();
+                        position
+                    })
+                    .collect()
+            }
+        };
+
+        for (entry, offset) in entries.iter().zip(offsets) {
+            let key = entry.get_key();
+
+            let previous = if entry.is_tombstone() {
+                self.dead_bytes += entry.size();
+                self.keydir.bump_version(entry.get_version());
+                self.keydir.remove(key)
+            } else {
+                self.keydir.put(
+                    key.as_slice(),
+                    KeyDirEntry::new(
+                        self.active_id,
+                        entry.get_value_size(),
+                        offset + entry.value_offset(),
+                        entry.get_timestamp(),
+                        entry.get_expire(),
+                        entry.get_version(),
+                    ),
+                )
+            };
+
+            if let Some(previous) = previous {
+                self.dead_bytes += entry_size(key, &previous);
+            }
+        }
+
+        Ok(self.keydir.len())
+    }
+
+    ///
+    /// Get the Value of a Key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The Value of the Key if it exists and did not expire
+    /// * Error raised
+    ///
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>, Error> {
+        match self.keydir.get(key) {
+            Some(location) if !location.is_expired(now()) => {
+                self.read_value(location).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    ///
+    /// Get the Keys and Values between two Keys in key order
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The smallest key to return
+    /// * `end` - The largest key to return
+    /// * `limit` - The maximum number of keys to return
+    /// * `reverse` - Whether to walk from the largest key down
+    ///
+    /// # Returns
+    ///
+    /// * The Keys that did not expire and their Values
+    /// * Error raised
+    ///
+    pub fn range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+        reverse: bool,
+    ) -> Result<Vec<Pair>, Error> {
+        let now = now();
+        let mut items: Vec<Pair> = Vec::new();
+
+        for (key, location) in self.keydir.range(start, Some(end), reverse) {
+            if items.len() >= limit {
+                break;
+            }
+
+            if !location.is_expired(now) {
+                items.push((key.to_vec(), self.read_value(location)?));
+            }
+        }
+
+        Ok(items)
+    }
 
-    assert_eq!(db.set("key1", "value1"), 1);
-    assert_eq!(db.set("key2", "value2"), 2);
-    assert_eq!(db.set("key3", "value3"), 3);
+    ///
+    /// Get the Value of a Key along with its version
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The Value and the version of the Key if it exists and did not expire
+    /// * Error raised
+    ///
+    pub fn get_versioned<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<(Vec<u8>, u64)>, Error> {
+        let version = self.version(key.as_ref());
+
+        Ok(self.get(key)?.map(|value| (value, version)))
+    }
+
+    ///
+    /// Gets the version of a Key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The version of the Key or zero if it does not exist or expired
+    ///
+    pub fn version<K: AsRef<[u8]>>(&self, key: K) -> u64 {
+        match self.keydir.get(key) {
+            Some(location) if !location.is_expired(now()) => location.get_version(),
+            _ => 0,
+        }
+    }
+
+    ///
+    /// Store or Update a Key Value if the Key is still at the expected version
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `value` - The value bytes
+    /// * `expire` - The unix timestamp the value expires at or zero
+    /// * `expected` - The current version of the Key or zero if it must not exist
+    ///
+    /// # Returns
+    ///
+    /// * The new version of the Key or None if its version changed
+    /// * Error raised
+    ///
+    pub fn set_if_version<S: Into<Vec<u8>>>(
+        &mut self,
+        key: S,
+        value: S,
+        expire: u64,
+        expected: u64,
+    ) -> Result<Option<u64>, Error> {
+        let key = key.into();
+
+        if self.version(&key) != expected {
+            return Ok(None);
+        }
+
+        self.apply(vec![Operation::Set(key.to_vec(), value.into(), expire)])?;
+
+        Ok(Some(self.version(&key)))
+    }
+
+    ///
+    /// Whether the Key exists and did not expire
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    pub fn exists<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        match self.keydir.get(key) {
+            Some(location) => !location.is_expired(now()),
+            None => false,
+        }
+    }
+
+    ///
+    /// Walks the keys in key order, a page at a time
+    ///
+    /// A page examines up to `count` keys and returns the ones that did not
+    /// expire and match the pattern, so it can hold fewer keys than `count`
+    /// or none while more keys remain. Every page is read from the current
+    /// keys, so keys written or removed during the walk show up only if
+    /// they sort at or after the key the next page starts at. The `btree`
+    /// index walks the keys of the page only, the `hash` index makes a pass
+    /// over every key for each page.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The key the page starts at, empty to start from the first key
+    /// * `count` - The number of keys to examine
+    /// * `pattern` - A glob pattern the keys must match, empty to match any key
+    ///
+    /// # Returns
+    ///
+    /// * The keys of the page and the key the next page starts at if any
+    ///
+    pub fn scan(
+        &self,
+        start: &[u8],
+        count: usize,
+        pattern: &[u8],
+    ) -> (Vec<Vec<u8>>, Option<Vec<u8>>) {
+        let now = now();
+        let mut page = self.keydir.scan(start, count.saturating_add(1));
+
+        let next = match page.len() > count {
+            true => page.pop().map(|(key, _)| key.to_vec()),
+            false => None,
+        };
+
+        let keys = page
+            .into_iter()
+            .filter(|(key, location)| {
+                !location.is_expired(now)
+                    && (pattern.is_empty() || matches(pattern, key))
+            })
+            .map(|(key, _)| key.to_vec())
+            .collect();
+
+        (keys, next)
+    }
+
+    ///
+    /// Lists the keys starting with a prefix in key order
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the keys, empty to list every key
+    ///
+    /// # Returns
+    ///
+    /// * The keys that did not expire
+    ///
+    pub fn keys(&self, prefix: &[u8]) -> Vec<Vec<u8>> {
+        let now = now();
+
+        self.keydir
+            .range(prefix, None, false)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter(|(_, location)| !location.is_expired(now))
+            .map(|(key, _)| key.to_vec())
+            .collect()
+    }
+
+    ///
+    /// The number of records in the database
+    ///
+    pub fn len(&self) -> usize {
+        self.keydir.len()
+    }
+
+    ///
+    /// Whether the database holds no records
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.keydir.is_empty()
+    }
+
+    ///
+    /// Removes the expired keys among a random sample of expiring keys
+    ///
+    /// Reads only hide expired keys, so the ones never read again stay in
+    /// the keydir until a sweep writes their tombstones.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The number of keys with an expiry to check
+    ///
+    /// # Returns
+    ///
+    /// * The number of removed keys
+    /// * Error raised
+    ///
+    pub fn sweep(&mut self, sample: usize) -> Result<usize, Error> {
+        let keys = self.sample_expired(sample);
+
+        self.remove_expired(keys)
+    }
+
+    ///
+    /// Gets the expired keys among a random sample of expiring keys
+    ///
+    /// Only the keys with an expiry are sampled, so it costs the sample size
+    /// whatever the number of keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The number of keys with an expiry to check
+    ///
+    /// # Returns
+    ///
+    /// * The expired keys
+    ///
+    pub fn sample_expired(&self, sample: usize) -> Vec<Vec<u8>> {
+        let now = now();
+
+        self.keydir
+            .sample_expiring(sample)
+            .into_iter()
+            .filter(|(_, location)| location.is_expired(now))
+            .map(|(key, _)| key.to_vec())
+            .collect()
+    }
+
+    ///
+    /// Writes the tombstones of keys found expired by a sample
+    ///
+    /// The keys written since the sample or not expired anymore are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The expired keys
+    ///
+    /// # Returns
+    ///
+    /// * The number of removed keys
+    /// * Error raised
+    ///
+    pub fn remove_expired(&mut self, keys: Vec<Vec<u8>>) -> Result<usize, Error> {
+        let now = now();
+        // Expired keys are hidden, so their tombstones skip the existence check
+        let entries: Vec<LogEntry> = keys
+            .into_iter()
+            .filter(|key| {
+                self.keydir
+                    .get(key)
+                    .is_some_and(|location| location.is_expired(now))
+            })
+            .map(|key| LogEntry::tombstone(key.as_slice()))
+            .collect();
+
+        if entries.is_empty() {
+            return Ok(0);
+        }
+
+        let count = entries.len();
+
+        self.write(entries)?;
+        self.expired_keys += count as u64;
+
+        Ok(count)
+    }
+
+    ///
+    /// Loads the database by rebuilding the keydir from the datafiles
+    ///
+    /// The last datafile found is considered sealed and writes go to a new one.
+    /// The CRC of the entries is not checked after a clean shutdown, its
+    /// marker is removed so a crash afterwards is verified again.
+    ///
+    /// # Returns
+    ///
+    /// * The number of records in the database
+    /// * Error raised
+    ///
+    pub fn load(&mut self) -> Result<usize, Error> {
+        let mut keydir = KeyDir::with_index(self.keydir.get_index_type());
+        let mut bootup = Bootup::new(self.path.as_str());
+        let marker = self.marker_path();
+
+        if Writer::new().file_exists(marker.to_string()) {
+            bootup.set_verify(false);
+            remove_file(marker.as_str())?;
+        }
+
+        let last_id = bootup.rebuild(&mut keydir)?;
+
+        self.keydir = keydir;
+        self.active = None;
+        self.active_id = last_id + 1;
+        self.active_size = 0;
+        self.measure()?;
+
+        Ok(self.keydir.len())
+    }
+
+    ///
+    /// Whether the dead bytes ratio reached the compaction threshold
+    ///
+    pub fn needs_compaction(&self) -> bool {
+        !self.compacting
+            && self.dead_bytes > 0
+            && self.dead_bytes as f64 >= self.total_bytes as f64 * self.compact_ratio
+    }
+
+    ///
+    /// Whether a compaction is merging the sealed datafiles
+    ///
+    pub fn is_compacting(&self) -> bool {
+        self.compacting
+    }
+
+    ///
+    /// Merges the sealed datafiles to reclaim the space of dead entries
+    ///
+    /// # Returns
+    ///
+    /// * The number of merged datafiles
+    /// * Error raised
+    ///
+    pub fn compact(&mut self) -> Result<usize, Error> {
+        let plan = match self.prepare_compaction()? {
+            Some(plan) => plan,
+            None => return Ok(0),
+        };
+
+        let merged = Compact::new(self.path.as_str(), self.max_file_size)
+            .merge(&plan, |key, file_id, value_pos| {
+                self.is_located_at(key, file_id, value_pos)
+            });
+
+        self.complete_compaction(plan, merged)
+    }
+
+    ///
+    /// Starts a compaction of the sealed datafiles
+    ///
+    /// The active datafile is sealed and writes continue in a datafile with
+    /// an id above the ones reserved for the merged datafiles, so bootup
+    /// keeps the last writer winning. Only the datafile ids are kept, the
+    /// merge checks the keydir entry by entry.
+    ///
+    /// # Returns
+    ///
+    /// * The compaction plan or None if no datafile is sealed
+    /// * Error raised
+    ///
+    pub fn prepare_compaction(&mut self) -> Result<Option<Plan>, Error> {
+        if self.compacting {
+            return Err(Error::State("A compaction is already running".to_string()));
+        }
+
+        let ids: Vec<u32> = self
+            .datafile_ids()?
+            .into_iter()
+            .filter(|id| *id < self.active_id)
+            .collect();
+
+        if ids.is_empty() {
+            return Ok(None);
+        }
+
+        let wt: Writer = Writer::new();
+        let mut total: u64 = 0;
+
+        for id in ids.iter() {
+            total += wt.filesize(self.datafile_path(*id))?;
+        }
+
+        // Two merged datafiles in a row always hold more than the size threshold
+        let reserved = (2 * (total / self.max_file_size.max(1)) + 2) as u32;
+        let first_id = self.active_id + 1;
+        let last_id = first_id + reserved - 1;
+
+        self.rotate_to(last_id + 1)?;
+        self.compacting = true;
+
+        Ok(Some(Plan::new(
+            ids,
+            first_id,
+            last_id,
+            self.keydir.get_version(),
+        )))
+    }
+
+    ///
+    /// Whether the keydir points a key at a datafile position
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `file_id` - The datafile id
+    /// * `value_pos` - The value offset in the datafile
+    ///
+    pub fn is_located_at(&self, key: &[u8], file_id: u32, value_pos: u64) -> bool {
+        self.keydir.get(key).is_some_and(|location| {
+            location.get_file_id() == file_id && location.get_value_pos() == value_pos
+        })
+    }
+
+    ///
+    /// Ends a compaction by swapping the merged datafiles in
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - The compaction plan
+    /// * `merged` - The outcome of the merge
+    ///
+    /// # Returns
+    ///
+    /// * The number of merged datafiles
+    /// * Error raised
+    ///
+    pub fn complete_compaction(
+        &mut self,
+        plan: Plan,
+        merged: Result<Merged, Error>,
+    ) -> Result<usize, Error> {
+        let compact = Compact::new(self.path.as_str(), self.max_file_size);
+
+        self.compacting = false;
+
+        let merged = match merged {
+            Ok(merged) => merged,
+            Err(e) => {
+                compact.abort();
+                return Err(e);
+            }
+        };
+
+        compact.install(&plan, merged, &mut self.keydir)?;
+        self.measure()?;
+
+        Ok(plan.get_ids().len())
+    }
+
+    ///
+    /// Flush the active datafile to disk
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn flush(&self) -> Result<(), Error> {
+        match &self.active {
+            Some(f) => f.sync_all().map_err(Error::from),
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Flushes and closes the active datafile
+    ///
+    /// The next write opens the active datafile again.
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn close(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.active = None;
+
+        Ok(())
+    }
+
+    ///
+    /// Flushes and closes the active datafile then marks the shutdown as clean
+    ///
+    /// A database never written has no directory and nothing to mark.
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        self.close()?;
+
+        if !Path::new(self.path.as_str()).is_dir() {
+            return Ok(());
+        }
+
+        File::create(self.marker_path())
+            .and_then(|f| f.sync_all())
+            .map_err(Error::from)
+    }
+
+    ///
+    /// Seals the active datafile, writes its hint file and opens the next one
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn rotate(&mut self) -> Result<(), Error> {
+        self.rotate_to(self.active_id + 1)
+    }
+
+    // Seals the active datafile and opens the datafile with the provided id
+    fn rotate_to(&mut self, file_id: u32) -> Result<(), Error> {
+        self.flush()?;
+        self.active = None;
+
+        let path = self.datafile_path(self.active_id);
+
+        if Writer::new().file_exists(path.to_string()) {
+            let entries = Hint::from_datafile(path)?;
+            Hint::new(self.hintfile_path(self.active_id)).write(&entries)?;
+        }
+
+        self.active_id = file_id;
+        self.active_size = 0;
+        self.open_active()
+    }
+
+    // Reads the value a keydir location points to
+    fn read_value(&self, location: &KeyDirEntry) -> Result<Vec<u8>, Error> {
+        self.read_at(location, 0, location.get_value_sz() as usize)
+    }
+
+    // Reads `len` bytes of the value a keydir entry points to from an offset
+    fn read_at(
+        &self,
+        location: &KeyDirEntry,
+        offset: u64,
+        len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let mut value = vec![0u8; len];
+
+        File::open(self.datafile_path(location.get_file_id())).and_then(|mut f| {
+            f.seek(SeekFrom::Start(location.get_value_pos() + offset))?;
+            f.read_exact(&mut value)
+        })?;
+
+        Ok(value)
+    }
+
+    // Gets the path of the clean shutdown marker
+    fn marker_path(&self) -> String {
+        format!("{}/{}", self.path, CLEAN_SHUTDOWN_FILE)
+    }
+
+    // Computes the datafiles size and the dead bytes from the keydir, the
+    // entries of expired keys are dead too
+    fn measure(&mut self) -> Result<(), Error> {
+        let wt: Writer = Writer::new();
+        let mut total: u64 = 0;
+
+        for id in self.datafile_ids()? {
+            total += wt.filesize(self.datafile_path(id))?;
+        }
+
+        let now = now();
+        let live: u64 = self
+            .keydir
+            .iter()
+            .filter(|(_, location)| !location.is_expired(now))
+            .map(|(key, location)| entry_size(key, location))
+            .sum();
+
+        self.total_bytes = total;
+        self.dead_bytes = total.saturating_sub(live);
+
+        Ok(())
+    }
+
+    // Opens the active datafile for appending
+    fn open_active(&mut self) -> Result<(), Error> {
+        create_dir_all(self.path.as_str())?;
+
+        let path = self.datafile_path(self.active_id);
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path.as_str())?;
+
+        self.active_size = Writer::new().filesize(path)?;
+        self.active = Some(file);
+
+        Ok(())
+    }
+
+    // Appends encoded records to the active datafile and returns their offset
+    fn append(&mut self, buf: &[u8]) -> Result<u64, Error> {
+        let size = buf.len() as u64;
+
+        if self.active.is_none() {
+            self.open_active()?;
+        }
+
+        // Records never span two datafiles, the active one is sealed
+        // first if the records would push it over the threshold
+        if self.active_size > 0 && self.active_size + size > self.max_file_size {
+            self.rotate()?;
+        }
+
+        let offset = self.active_size;
+
+        if let Some(f) = self.active.as_mut() {
+            // A failed write may leave part of the records behind, the file
+            // is cut back so later offsets still match the file contents,
+            // and if that fails too the torn tail is sealed and left to the
+            // readers which stop at the first truncated entry
+            if let Err(e) = f.write_all(buf) {
+                if f.set_len(self.active_size).is_err() {
+                    self.active = None;
+                    let _ = self.rotate_to(self.active_id + 1);
+                }
+                return Err(e.into());
+            }
+        }
+
+        self.active_size += size;
+        self.total_bytes += size;
+
+        Ok(offset)
+    }
+}
+
+// The size of the datafile entry a keydir location points to
+fn entry_size(key: &[u8], location: &KeyDirEntry) -> u64 {
+    (HEADER_SIZE + key.len()) as u64 + location.get_value_sz() as u64
+}
+
+#[test]
+fn test_database_methods() {
+    let _ = std::fs::remove_dir_all("./cache/database_methods");
+
+    let mut db: Database = Database::new("./cache/langmore");
+
+    db.set_path("./cache/database_methods");
+
+    assert_eq!(db.get_path(), "./cache/database_methods".to_string());
+    assert_eq!(
+        db.datafile_path(1),
+        "./cache/database_methods/1.data".to_string()
+    );
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set("key2", "value2"), Ok(2));
+    assert_eq!(db.set("key3", "value3"), Ok(3));
+
+    assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db.get("key2"), Ok(Some(b"value2".to_vec())));
+    assert_eq!(db.get("key3"), Ok(Some(b"value3".to_vec())));
+
+    assert_eq!(db.remove("key1"), Ok(2));
+    assert_eq!(db.remove("key2"), Ok(1));
+    assert_eq!(db.remove("key3"), Ok(0));
+    assert_eq!(db.remove("key3"), Ok(0));
+
+    assert_eq!(db.get("key1"), Ok(None));
+    assert!(!db.exists("key1"));
+    assert!(db.is_empty());
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set("key1", ""), Ok(1));
+    assert_eq!(db.set("key2", "value2"), Ok(2));
+
+    assert_eq!(db.get("key1"), Ok(Some(b"".to_vec())));
+    assert_eq!(db.get("key2"), Ok(Some(b"value2".to_vec())));
+    assert_eq!(db.len(), 2);
+
+    assert_eq!(db.flush(), Ok(()));
+
+    let mut db2: Database = Database::new("./cache/database_methods");
+
+    assert_eq!(db2.load(), Ok(2));
+    assert_eq!(db2.get_active_id(), 2);
+    assert_eq!(db2.get("key1"), Ok(Some(b"".to_vec())));
+    assert_eq!(db2.get("key2"), Ok(Some(b"value2".to_vec())));
+    assert_eq!(db2.get("key3"), Ok(None));
+}
+
+#[test]
+fn test_database_failed_write() {
+    let _ = std::fs::remove_dir_all("./cache/database_failed_write");
+
+    let mut db: Database = Database::new("./cache/database_failed_write");
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+
+    // A torn write leaves bytes behind on a handle that can't write anymore
+    let path = db.datafile_path(db.get_active_id());
+    let mut torn = OpenOptions::new().append(true).open(&path).unwrap();
+
+    torn.write_all(b"torn").unwrap();
+    db.active = Some(File::open(&path).unwrap());
+
+    assert!(db.set("key2", "value2").is_err());
+    assert_eq!(db.set("key3", "value3"), Ok(2));
+    assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db.get("key2"), Ok(None));
+    assert_eq!(db.get("key3"), Ok(Some(b"value3".to_vec())));
+    assert_eq!(db.flush(), Ok(()));
+
+    let mut db2: Database = Database::new("./cache/database_failed_write");
+
+    assert_eq!(db2.load(), Ok(2));
+    assert_eq!(db2.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db2.get("key3"), Ok(Some(b"value3".to_vec())));
+}
+
+#[test]
+fn test_database_rotation() {
+    let _ = std::fs::remove_dir_all("./cache/database_rotation");
+
+    let mut db: Database = Database::new("./cache/database_rotation");
+    let wt: Writer = Writer::new();
+
+    // Each entry is 46 bytes so two entries fit in a datafile
+    db.set_max_file_size(92);
+
+    assert_eq!(db.get_max_file_size(), 92);
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set("key2", "value2"), Ok(2));
+    assert_eq!(db.get_active_id(), 1);
+    assert_eq!(db.set("key3", "value3"), Ok(3));
+    assert_eq!(db.get_active_id(), 2);
+
+    assert_eq!(wt.filesize(db.datafile_path(1)), Ok(92));
+    assert_eq!(wt.filesize(db.datafile_path(2)), Ok(46));
+
+    assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db.get("key3"), Ok(Some(b"value3".to_vec())));
+
+    assert!(wt.file_exists(db.hintfile_path(1)));
+    assert!(!wt.file_exists(db.hintfile_path(2)));
+
+    assert_eq!(db.rotate(), Ok(()));
+    assert_eq!(db.get_active_id(), 3);
+    assert!(wt.file_exists(db.hintfile_path(2)));
+    assert!(wt.file_exists(db.datafile_path(3)));
+}
+
+#[test]
+fn test_database_shutdown() {
+    let _ = std::fs::remove_dir_all("./cache/database_shutdown");
+
+    let mut db: Database = Database::new("./cache/database_shutdown");
+    let wt: Writer = Writer::new();
+    let marker = format!("./cache/database_shutdown/{}", CLEAN_SHUTDOWN_FILE);
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.shutdown(), Ok(()));
+    assert!(wt.file_exists(marker.to_string()));
+
+    // A corrupted value is trusted after a clean shutdown only
+    let path = db.datafile_path(1);
+    let mut data = std::fs::read(path.as_str()).unwrap();
+    let last = data.len() - 1;
+
+    data[last] ^= 0xff;
+    let _ = std::fs::write(path.as_str(), &data);
+
+    let mut db: Database = Database::new("./cache/database_shutdown");
+
+    assert_eq!(db.load(), Ok(1));
+    assert!(!wt.file_exists(marker.to_string()));
+
+    let _ = std::fs::remove_file(db.hintfile_path(1));
+    let mut db: Database = Database::new("./cache/database_shutdown");
+
+    assert_eq!(db.load(), Ok(0));
+}
+
+#[test]
+fn test_database_compact() {
+    let _ = std::fs::remove_dir_all("./cache/database_compact");
+
+    let mut db: Database = Database::new("./cache/database_compact");
+    let wt: Writer = Writer::new();
+
+    db.set_max_file_size(92);
+
+    assert_eq!(db.get_compact_ratio(), DEFAULT_COMPACT_RATIO);
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set("key2", "value2"), Ok(2));
+    assert_eq!(db.set("key1", "value3"), Ok(2));
+    assert_eq!(db.set("key3", "value4"), Ok(3));
+    assert_eq!(db.remove("key2"), Ok(2));
+
+    // 1.data [key1, key2] 2.data [key1, key3] 3.data [tombstone key2]
+    assert_eq!(db.get_active_id(), 3);
+    assert_eq!(db.get_total_bytes(), 224);
+    assert_eq!(db.get_dead_bytes(), 132);
+    assert!(db.needs_compaction());
+
+    assert_eq!(db.compact(), Ok(2));
+
+    // 4.data [key3, key1] and writes continue in 10.data after the 6 ids
+    // reserved for the 184 bytes of the two sealed datafiles
+    assert!(!wt.file_exists(db.datafile_path(1)));
+    assert!(!wt.file_exists(db.datafile_path(2)));
+    assert!(wt.file_exists(db.hintfile_path(3)));
+    assert!(wt.file_exists(db.hintfile_path(4)));
+    assert!(!wt.file_exists(db.datafile_path(5)));
+    assert_eq!(db.get_active_id(), 10);
+    assert_eq!(db.get_total_bytes(), 132);
+    assert_eq!(db.get_dead_bytes(), 40);
+    assert!(!db.needs_compaction());
+
+    assert_eq!(db.get("key1"), Ok(Some(b"value3".to_vec())));
+    assert_eq!(db.get("key2"), Ok(None));
+    assert_eq!(db.get("key3"), Ok(Some(b"value4".to_vec())));
+
+    let mut db2: Database = Database::new("./cache/database_compact");
+
+    assert_eq!(db2.load(), Ok(2));
+    assert_eq!(db2.get("key1"), Ok(Some(b"value3".to_vec())));
+    assert_eq!(db2.get("key2"), Ok(None));
+}
+
+#[test]
+fn test_database_compact_concurrent() {
+    let _ = std::fs::remove_dir_all("./cache/database_compact_concurrent");
+
+    let mut db: Database = Database::new("./cache/database_compact_concurrent");
+
+    db.set_max_file_size(92);
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set("key2", "value2"), Ok(2));
+    assert_eq!(db.set("key3", "value3"), Ok(3));
+    assert_eq!(db.set("key1", "value4"), Ok(3));
+
+    let plan = db.prepare_compaction().unwrap().unwrap();
+
+    assert!(db.is_compacting());
+    assert!(!db.needs_compaction());
+    assert!(db.prepare_compaction().is_err());
+
+    // Writes made while merging win over the merged entries
+    assert_eq!(db.set("key2", "value5"), Ok(3));
+    assert_eq!(db.remove("key3"), Ok(2));
+
+    let merged = Compact::new(db.get_path(), 92)
+        .merge(&plan, |key, id, pos| db.is_located_at(key, id, pos));
+
+    // A write made after its entry was merged wins too
+    assert_eq!(db.set("key1", "value6"), Ok(2));
+
+    assert_eq!(db.complete_compaction(plan, merged), Ok(1));
+    assert!(!db.is_compacting());
+    assert_eq!(db.get("key1"), Ok(Some(b"value6".to_vec())));
+    assert_eq!(db.get("key2"), Ok(Some(b"value5".to_vec())));
+    assert_eq!(db.get("key3"), Ok(None));
+    assert_eq!(db.close(), Ok(()));
+
+    let mut db2: Database = Database::new("./cache/database_compact_concurrent");
+
+    assert_eq!(db2.load(), Ok(2));
+    assert_eq!(db2.get("key1"), Ok(Some(b"value6".to_vec())));
+    assert_eq!(db2.get("key2"), Ok(Some(b"value5".to_vec())));
+    assert_eq!(db2.get("key3"), Ok(None));
+}
+
+#[test]
+fn test_database_apply() {
+    let _ = std::fs::remove_dir_all("./cache/database_apply");
+
+    let mut db: Database = Database::new("./cache/database_apply");
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(
+        db.apply(vec![
+            Operation::Set(b"key2".to_vec(), b"value2".to_vec(), 0),
+            Operation::Delete(b"key1".to_vec()),
+            Operation::Delete(b"key1".to_vec()),
+            Operation::Set(b"key3".to_vec(), b"value3".to_vec(), 0),
+            Operation::Delete(b"key3".to_vec()),
+        ]),
+        Ok(1)
+    );
+
+    // 1 entry of 46 bytes then a batch header, 2 entries and 2 tombstones
+    assert_eq!(db.get_total_bytes(), 46 + 36 + 46 + 40 + 46 + 40);
+    assert_eq!(db.get_dead_bytes(), 46 + 36 + 40 + 46 + 40);
+    assert_eq!(db.get("key1"), Ok(None));
+    assert_eq!(db.get("key2"), Ok(Some(b"value2".to_vec())));
+    assert_eq!(db.get("key3"), Ok(None));
+    assert_eq!(db.apply(vec![Operation::Delete(b"key9".to_vec())]), Ok(1));
+
+    let mut db2: Database = Database::new("./cache/database_apply");
+
+    assert_eq!(db2.load(), Ok(1));
+    assert_eq!(db2.get("key2"), Ok(Some(b"value2".to_vec())));
+    assert_eq!(db2.get_dead_bytes(), db.get_dead_bytes());
+}
+
+#[test]
+fn test_database_expire() {
+    let _ = std::fs::remove_dir_all("./cache/database_expire");
+
+    let mut db: Database = Database::new("./cache/database_expire");
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(db.set_with_ttl("key2", "value2", 100), Ok(2));
+    assert_eq!(
+        db.apply(vec![Operation::Set(
+            b"key3".to_vec(),
+            b"value3".to_vec(),
+            1652000000,
+        )]),
+        Ok(3)
+    );
+
+    assert_eq!(db.ttl("key1"), Some(-1));
+    assert!(db.ttl("key2").unwrap() > 98);
+    assert_eq!(db.ttl("key3"), None);
+    assert_eq!(db.ttl("key4"), None);
+
+    assert_eq!(db.get("key3"), Ok(None));
+    assert!(!db.exists("key3"));
+    assert_eq!(db.remove("key3"), Ok(3));
+
+    assert_eq!(db.expire("key1", 50), Ok(true));
+    assert!(db.ttl("key1").unwrap() > 48);
+    assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db.persist("key2"), Ok(true));
+    assert_eq!(db.ttl("key2"), Some(-1));
+    assert_eq!(db.persist("key3"), Ok(false));
+    assert_eq!(db.expire("key3", 50), Ok(false));
+    assert_eq!(db.expire("key2", 0), Ok(true));
+    assert!(!db.exists("key2"));
+
+    let mut db2: Database = Database::new("./cache/database_expire");
+
+    assert_eq!(db2.load(), Ok(1));
+    assert!(db2.ttl("key1").unwrap() > 48);
+    assert_eq!(db2.get("key3"), Ok(None));
+}
+
+#[test]
+fn test_database_sweep() {
+    let _ = std::fs::remove_dir_all("./cache/database_sweep");
+
+    let mut db: Database = Database::new("./cache/database_sweep");
+    let expired = |key: &str| Operation::Set(key.into(), b"value".to_vec(), 1);
+
+    assert_eq!(db.set("key1", "value1"), Ok(1));
+    assert_eq!(
+        db.apply(vec![expired("key3"), expired("key4"), expired("key5")]),
+        Ok(4)
+    );
+
+    // Only the keys with an expiry are sampled
+    assert_eq!(db.sweep(1), Ok(1));
+    assert_eq!(db.len(), 3);
+    assert_eq!(db.set_with_ttl("key2", "value2", 100), Ok(4));
+    assert_eq!(db.sweep(10), Ok(2));
+    assert_eq!(db.len(), 2);
+    assert_eq!(db.sweep(10), Ok(0));
+    assert_eq!(db.get_expired_keys(), 3);
+    assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+    assert_eq!(db.get("key2"), Ok(Some(b"value2".to_vec())));
+
+    // A key written again after the sample is kept
+    assert_eq!(db.apply(vec![expired("key6")]), Ok(3));
+
+    let keys = db.sample_expired(10);
+
+    assert_eq!(keys, vec![b"key6".to_vec()]);
+    assert_eq!(db.set("key6", "value6"), Ok(3));
+    assert_eq!(db.remove_expired(keys), Ok(0));
+    assert_eq!(db.get("key6"), Ok(Some(b"value6".to_vec())));
+}
+
+#[test]
+fn test_database_versions() {
+    let _ = std::fs::remove_dir_all("./cache/database_versions");
+
+    let mut db: Database = Database::new("./cache/database_versions");
+
+    assert_eq!(db.version("key1"), 0);
+    assert_eq!(db.set_if_version("key1", "value1", 0, 0), Ok(Some(1)));
+    assert_eq!(db.set_if_version("key1", "value2", 0, 0), Ok(None));
+    assert_eq!(db.set_if_version("key1", "value2", 0, 1), Ok(Some(2)));
+    assert_eq!(db.get_versioned("key1"), Ok(Some((b"value2".to_vec(), 2))));
+    assert_eq!(db.set("key2", "value3"), Ok(2));
+    assert_eq!(db.version("key2"), 3);
+    assert_eq!(db.remove("key1"), Ok(1));
+    assert_eq!(db.get_versioned("key1"), Ok(None));
+
+    // The tombstone holding the highest version survives compaction
+    assert_eq!(db.rotate(), Ok(()));
+    assert_eq!(db.compact(), Ok(1));
+
+    let mut db2: Database = Database::new("./cache/database_versions");
+
+    assert_eq!(db2.load(), Ok(1));
+    assert_eq!(db2.version("key2"), 3);
+    assert_eq!(db2.set_if_version("key1", "value4", 0, 0), Ok(Some(5)));
+}
+
+#[test]
+fn test_database_scan() {
+    let _ = std::fs::remove_dir_all("./cache/database_scan");
+
+    let mut db: Database = Database::new("./cache/database_scan");
+    let expired = |key: &str| Operation::Set(key.into(), b"value".to_vec(), 1);
+
+    for key in ["user:2", "user:1", "order:1", "user:3"] {
+        assert!(db.set(key, "value").is_ok());
+    }
+
+    assert!(db.apply(vec![expired("user:0")]).is_ok());
+
+    // The expired key is examined but not returned
+    assert_eq!(
+        db.scan(b"", 3, b""),
+        (
+            vec![b"order:1".to_vec(), b"user:1".to_vec()],
+            Some(b"user:2".to_vec())
+        )
+    );
+    assert_eq!(
+        db.scan(b"user:2", 3, b"*:3"),
+        (vec![b"user:3".to_vec()], None)
+    );
+    assert_eq!(db.scan(b"user:4", 3, b""), (Vec::new(), None));
+    assert_eq!(
+        db.keys(b"user:"),
+        vec![b"user:1".to_vec(), b"user:2".to_vec(), b"user:3".to_vec()]
+    );
+    assert_eq!(db.keys(b"").len(), 4);
+}
+
+#[test]
+fn test_database_range() {
+    let _ = std::fs::remove_dir_all("./cache/database_range");
+
+    let mut db: Database = Database::new("./cache/database_range");
+    let pair =
+        |key: &str, value: &str| (key.as_bytes().to_vec(), value.as_bytes().to_vec());
+
+    db.set_index_type(IndexType::BTree);
+
+    assert_eq!(db.get_index_type(), IndexType::BTree);
+
+    for (key, value) in [("events:3", "c"), ("events:1", "a"), ("users:1", "u")] {
+        assert!(db.set(key, value).is_ok());
+    }
+
+    assert!(db
+        .apply(vec![Operation::Set(b"events:2".to_vec(), b"b".to_vec(), 1)])
+        .is_ok());
+
+    assert_eq!(
+        db.range(b"events:", b"events:~", 10, false),
+        Ok(vec![pair("events:1", "a"), pair("events:3", "c")])
+    );
+    assert_eq!(
+        db.range(b"events:", b"users:1", 2, true),
+        Ok(vec![pair("users:1", "u"), pair("events:3", "c")])
+    );
+    assert_eq!(db.range(b"users:", b"events:", 10, false), Ok(Vec::new()));
+
+    // The keys are kept when the index changes and after a reload
+    db.set_index_type(IndexType::Hash);
+
+    assert_eq!(
+        db.keys(b"events:"),
+        vec![b"events:1".to_vec(), b"events:3".to_vec()]
+    );
+    assert_eq!(db.version("users:1"), 3);
+    assert_eq!(db.load(), Ok(3));
+    assert_eq!(db.get_index_type(), IndexType::Hash);
+}
+
+#[test]
+fn test_database_increment() {
+    let _ = std::fs::remove_dir_all("./cache/database_increment");
+
+    let mut db: Database = Database::new("./cache/database_increment");
+
+    assert_eq!(db.increment("key1", 1), Ok(Some(1)));
+    assert_eq!(db.increment("key1", 10), Ok(Some(11)));
+    assert_eq!(db.increment("key1", -12), Ok(Some(-1)));
+    assert_eq!(db.get("key1"), Ok(Some(b"-1".to_vec())));
+    assert_eq!(db.set_with_ttl("key2", "9", 100), Ok(2));
+    assert_eq!(db.increment("key2", 1), Ok(Some(10)));
+    assert!(db.ttl("key2").unwrap() > 98);
+    assert_eq!(db.set("key3", "value3"), Ok(3));
+    assert_eq!(db.increment("key3", 1), Ok(None));
+    assert_eq!(db.increment("key1", i64::MIN), Ok(None));
+    assert_eq!(db.get("key1"), Ok(Some(b"-1".to_vec())));
+
+    let mut db2: Database = Database::new("./cache/database_increment");
+
+    assert_eq!(db2.load(), Ok(3));
+    assert_eq!(db2.get("key2"), Ok(Some(b"10".to_vec())));
+}
+
+#[test]
+fn test_database_append() {
+    let _ = std::fs::remove_dir_all("./cache/database_append");
 
-    assert_eq!(db.get("key1"), "value1".to_string());
-    assert_eq!(db.get("key2"), "value2".to_string());
-    assert_eq!(db.get("key3"), "value3".to_string());
+    let mut db: Database = Database::new("./cache/database_append");
 
-    assert_eq!(db.remove("key1"), 2);
-    assert_eq!(db.remove("key2"), 1);
-    assert_eq!(db.remove("key3"), 0);
+    assert_eq!(db.append_value("key1", "Hello"), Ok(5));
+    assert_eq!(db.append_value("key1", " World"), Ok(11));
+    assert_eq!(db.strlen("key1"), 11);
+    assert_eq!(db.strlen("key2"), 0);
+    assert_eq!(db.get_range("key1", 0, 4), Ok(b"Hello".to_vec()));
+    assert_eq!(db.get_range("key1", -5, -1), Ok(b"World".to_vec()));
+    assert_eq!(db.get_range("key1", 6, 100), Ok(b"World".to_vec()));
+    assert_eq!(db.get_range("key1", -100, 0), Ok(b"H".to_vec()));
+    assert_eq!(db.get_range("key1", 5, 2), Ok(Vec::new()));
+    assert_eq!(db.get_range("key1", 20, 30), Ok(Vec::new()));
+    assert_eq!(db.get_range("key2", 0, -1), Ok(Vec::new()));
+    assert_eq!(db.set_with_ttl("key3", "abc", 100), Ok(2));
+    assert_eq!(db.append_value("key3", "def"), Ok(6));
+    assert!(db.ttl("key3").unwrap() > 98);
 
-    assert_eq!(db.set("key1", "value1"), 1);
-    assert_eq!(db.set("key2", "value2"), 2);
-    assert_eq!(db.set("key3", "value3"), 3);
+    db.set_max_value_size(8);
 
-    db.flush();
+    assert_eq!(
+        db.append_value("key3", "ghi"),
+        Err(Error::State(
+            "The value can not grow over 8 bytes".to_string()
+        ))
+    );
+    assert_eq!(db.append_value("key3", "gh"), Ok(8));
 
-    let db2: Database = db.load();
+    let mut db2: Database = Database::new("./cache/database_append");
 
-    assert_eq!(db2.get("key1"), "value1".to_string());
-    assert_eq!(db2.get("key2"), "value2".to_string());
-    assert_eq!(db2.get("key3"), "value3".to_string());
+    assert_eq!(db2.load(), Ok(2));
+    assert_eq!(db2.get("key1"), Ok(Some(b"Hello World".to_vec())));
 }
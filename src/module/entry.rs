@@ -0,0 +1,619 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::error::Error;
+
+use crc32fast::Hasher;
+
+use std::convert::TryInto;
+use std::io::{ErrorKind, Read};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The size of the entry header: CRC, timestamp, expire, version, key size and
+/// value size
+pub const HEADER_SIZE: usize = 36;
+
+/// The value size reserved to mark an entry as a tombstone
+pub const TOMBSTONE: u32 = u32::MAX;
+
+/// The key size reserved to mark a record as a batch of entries
+pub const BATCH: u32 = u32::MAX;
+
+#[derive(Debug, PartialEq, Clone)]
+// LogEntry type
+pub struct LogEntry {
+    // The CRC32 of the entry payload
+    crc: u32,
+    // The entry unix timestamp
+    timestamp: u64,
+    // The unix timestamp the entry expires at or zero if it never expires
+    expire: u64,
+    // The database wide write sequence number or zero if unversioned
+    version: u64,
+    // The key size in bytes
+    key_size: u32,
+    // The value size in bytes or TOMBSTONE
+    value_size: u32,
+    // The actual key
+    key: Vec<u8>,
+    // The actual value
+    value: Vec<u8>,
+}
+
+// LogEntry type methods
+impl LogEntry {
+    ///
+    /// Returns a log entry for the key and value stamped with the current time
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The entry key
+    /// * `value` - The entry value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::entry::LogEntry;
+    ///
+    /// let entry: LogEntry = LogEntry::new("key", "value");
+    /// ```
+    ///
+    pub fn new<S: Into<Vec<u8>>>(key: S, value: S) -> LogEntry {
+        LogEntry::with_timestamp(key, value, now())
+    }
+
+    ///
+    /// Returns a log entry that expires at the provided time
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The entry key
+    /// * `value` - The entry value
+    /// * `expire` - The unix timestamp the entry expires at or zero
+    ///
+    pub fn with_expire<S: Into<Vec<u8>>>(key: S, value: S, expire: u64) -> LogEntry {
+        let mut entry = LogEntry::new(key, value);
+
+        entry.expire = expire;
+        entry.crc = entry.checksum();
+        entry
+    }
+
+    ///
+    /// Returns a log entry with an explicit timestamp
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The entry key
+    /// * `value` - The entry value
+    /// * `timestamp` - The entry unix timestamp
+    ///
+    pub fn with_timestamp<S: Into<Vec<u8>>>(
+        key: S,
+        value: S,
+        timestamp: u64,
+    ) -> LogEntry {
+        let key = key.into();
+        let value = value.into();
+
+        let mut entry = LogEntry {
+            crc: 0,
+            timestamp,
+            expire: 0,
+            version: 0,
+            key_size: key.len() as u32,
+            value_size: value.len() as u32,
+            key,
+            value,
+        };
+
+        entry.crc = entry.checksum();
+        entry
+    }
+
+    ///
+    /// Returns a tombstone entry that marks the key as deleted
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The deleted key
+    ///
+    pub fn tombstone<S: Into<Vec<u8>>>(key: S) -> LogEntry {
+        let key = key.into();
+
+        let mut entry = LogEntry {
+            crc: 0,
+            timestamp: now(),
+            expire: 0,
+            version: 0,
+            key_size: key.len() as u32,
+            value_size: TOMBSTONE,
+            key,
+            value: Vec::new(),
+        };
+
+        entry.crc = entry.checksum();
+        entry
+    }
+
+    pub fn get_crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn get_expire(&self) -> u64 {
+        self.expire
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get_key_size(&self) -> u32 {
+        self.key_size
+    }
+
+    pub fn get_value_size(&self) -> u32 {
+        self.value_size
+    }
+
+    pub fn get_key(&self) -> &Vec<u8> {
+        &self.key
+    }
+
+    pub fn get_value(&self) -> &Vec<u8> {
+        &self.value
+    }
+
+    ///
+    /// Stamps the entry with a version and updates its CRC
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The database wide write sequence number
+    ///
+    pub fn set_version(&mut self, version: u64) {
+        self.version = version;
+        self.crc = self.checksum();
+    }
+
+    ///
+    /// Whether the entry marks its key as deleted
+    ///
+    pub fn is_tombstone(&self) -> bool {
+        self.value_size == TOMBSTONE
+    }
+
+    ///
+    /// Whether the entry expired at the provided time
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current unix timestamp
+    ///
+    pub fn is_expired(&self, now: u64) -> bool {
+        is_expired(self.expire, now)
+    }
+
+    ///
+    /// The position of the value relative to the start of the entry
+    ///
+    pub fn value_offset(&self) -> u64 {
+        (HEADER_SIZE + self.key.len()) as u64
+    }
+
+    ///
+    /// The size of the encoded entry in bytes
+    ///
+    pub fn size(&self) -> u64 {
+        (HEADER_SIZE + self.key.len() + self.value.len()) as u64
+    }
+
+    ///
+    /// Encodes the entry as
+    /// `CRC | timestamp | expire | version | key_size | value_size | key | value`
+    ///
+    /// # Returns
+    ///
+    /// * The entry bytes
+    ///
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size() as usize);
+
+        buf.extend_from_slice(&self.crc.to_be_bytes());
+        buf.extend_from_slice(&self.payload());
+        buf
+    }
+
+    ///
+    /// Decodes an entry from the start of the buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The encoded entry bytes
+    ///
+    /// # Returns
+    ///
+    /// * The decoded entry
+    /// * Error raised if the buffer is truncated or the CRC mismatches
+    ///
+    pub fn decode(buf: &[u8]) -> Result<LogEntry, Error> {
+        let mut slice = buf;
+
+        match LogEntry::read_from(&mut slice)? {
+            Some(entry) => Ok(entry),
+            None => Err(Error::Corruption("empty entry".to_string())),
+        }
+    }
+
+    ///
+    /// Reads the next entry from a stream
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The stream to read from
+    ///
+    /// # Returns
+    ///
+    /// * The entry or None at a clean end of stream
+    /// * Error raised if the entry is truncated, a batch or the CRC mismatches
+    ///
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Option<LogEntry>, Error> {
+        match read_record(reader)? {
+            Some(Record::Entry(entry)) => Ok(Some(entry)),
+            Some(Record::Batch(_)) => {
+                Err(Error::Corruption("unexpected batch".to_string()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // The bytes covered by the CRC
+    fn payload(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size() as usize - 4);
+
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(&self.expire.to_be_bytes());
+        buf.extend_from_slice(&self.version.to_be_bytes());
+        buf.extend_from_slice(&self.key_size.to_be_bytes());
+        buf.extend_from_slice(&self.value_size.to_be_bytes());
+        buf.extend_from_slice(&self.key);
+        buf.extend_from_slice(&self.value);
+        buf
+    }
+
+    // Computes the CRC32 of the entry payload
+    fn checksum(&self) -> u32 {
+        let mut hasher = Hasher::new();
+        hasher.update(&self.payload());
+        hasher.finalize()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+// Record type
+pub enum Record {
+    // A single entry
+    Entry(LogEntry),
+    // Entries written atomically
+    Batch(Vec<LogEntry>),
+}
+
+///
+/// Encodes entries as one batch record
+///
+/// The record is `CRC | timestamp | 0 | 0 | BATCH | payload_size | entries` where the
+/// CRC covers the whole payload, so a batch torn by a crash is discarded as
+/// a whole on recovery.
+///
+/// # Arguments
+///
+/// * `entries` - The entries of the batch
+///
+/// # Returns
+///
+/// * The batch record bytes
+///
+pub fn encode_batch(entries: &[LogEntry]) -> Vec<u8> {
+    let mut payload: Vec<u8> = Vec::new();
+
+    for entry in entries.iter() {
+        payload.extend_from_slice(&entry.encode());
+    }
+
+    let mut body = Vec::with_capacity(HEADER_SIZE - 4 + payload.len());
+
+    body.extend_from_slice(&now().to_be_bytes());
+    body.extend_from_slice(&0u64.to_be_bytes());
+    body.extend_from_slice(&0u64.to_be_bytes());
+    body.extend_from_slice(&BATCH.to_be_bytes());
+    body.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    body.extend_from_slice(&payload);
+
+    let mut hasher = Hasher::new();
+    hasher.update(&body);
+
+    let mut buf = Vec::with_capacity(HEADER_SIZE + payload.len());
+
+    buf.extend_from_slice(&hasher.finalize().to_be_bytes());
+    buf.extend_from_slice(&body);
+    buf
+}
+
+///
+/// Reads the next entry or batch from a stream
+///
+/// # Arguments
+///
+/// * `reader` - The stream to read from
+///
+/// # Returns
+///
+/// * The record or None at a clean end of stream
+/// * Error raised if the record is truncated or the CRC mismatches
+///
+pub fn read_record<R: Read>(reader: &mut R) -> Result<Option<Record>, Error> {
+    read(reader, true)
+}
+
+///
+/// Reads the next entry or batch from a stream without checking its CRC
+///
+/// Used on datafiles written before a clean shutdown, a truncated record
+/// is still reported.
+///
+/// # Arguments
+///
+/// * `reader` - The stream to read from
+///
+/// # Returns
+///
+/// * The record or None at a clean end of stream
+/// * Error raised if the record is truncated
+///
+pub fn read_record_unverified<R: Read>(reader: &mut R) -> Result<Option<Record>, Error> {
+    read(reader, false)
+}
+
+// Reads the next entry or batch from a stream
+fn read<R: Read>(reader: &mut R, verify: bool) -> Result<Option<Record>, Error> {
+    let mut header = [0u8; HEADER_SIZE];
+
+    match reader.read_exact(&mut header[0..1]) {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::from(e)),
+    }
+
+    reader
+        .read_exact(&mut header[1..])
+        .map_err(|e| Error::Corruption(format!("truncated entry header: {}", e)))?;
+
+    let crc = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    let timestamp = u64::from_be_bytes(header[4..12].try_into().unwrap());
+    let expire = u64::from_be_bytes(header[12..20].try_into().unwrap());
+    let version = u64::from_be_bytes(header[20..28].try_into().unwrap());
+    let key_size = u32::from_be_bytes(header[28..32].try_into().unwrap());
+    let value_size = u32::from_be_bytes(header[32..36].try_into().unwrap());
+
+    if key_size == BATCH {
+        let payload = read_bytes(reader, value_size as u64)
+            .map_err(|e| Error::Corruption(format!("truncated batch: {}", e)))?;
+
+        if verify {
+            let mut hasher = Hasher::new();
+            hasher.update(&header[4..]);
+            hasher.update(&payload);
+
+            if hasher.finalize() != crc {
+                return Err(Error::Corruption("batch CRC mismatch".to_string()));
+            }
+        }
+
+        let mut entries: Vec<LogEntry> = Vec::new();
+        let mut slice = payload.as_slice();
+
+        loop {
+            match read(&mut slice, verify)? {
+                Some(Record::Entry(entry)) => entries.push(entry),
+                Some(Record::Batch(_)) => {
+                    return Err(Error::Corruption("unexpected batch".to_string()))
+                }
+                None => break,
+            }
+        }
+
+        return Ok(Some(Record::Batch(entries)));
+    }
+
+    let key = read_bytes(reader, key_size as u64);
+    let value = match value_size {
+        TOMBSTONE => Ok(Vec::new()),
+        size => read_bytes(reader, size as u64),
+    };
+
+    let entry = match (key, value) {
+        (Ok(key), Ok(value)) => LogEntry {
+            crc,
+            timestamp,
+            expire,
+            version,
+            key_size,
+            value_size,
+            key,
+            value,
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            return Err(Error::Corruption(format!("truncated entry: {}", e)));
+        }
+    };
+
+    if verify && entry.checksum() != crc {
+        return Err(Error::Corruption("entry CRC mismatch".to_string()));
+    }
+
+    Ok(Some(Record::Entry(entry)))
+}
+
+// Reads exactly size bytes without trusting size for the allocation
+fn read_bytes<R: Read>(reader: &mut R, size: u64) -> std::io::Result<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::new();
+
+    reader.take(size).read_to_end(&mut buf)?;
+
+    if (buf.len() as u64) < size {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof));
+    }
+
+    Ok(buf)
+}
+
+///
+/// Whether an expiry timestamp passed, zero never expires
+///
+/// # Arguments
+///
+/// * `expire` - The unix timestamp to expire at or zero
+/// * `now` - The current unix timestamp
+///
+pub fn is_expired(expire: u64, now: u64) -> bool {
+    expire != 0 && expire <= now
+}
+
+///
+/// Returns the expiry timestamp of a time to live, zero never expires
+///
+/// # Arguments
+///
+/// * `ttl` - The time to live in seconds or zero
+///
+pub fn expire_at(ttl: u64) -> u64 {
+    match ttl {
+        0 => 0,
+        ttl => now() + ttl,
+    }
+}
+
+///
+/// Returns the current unix timestamp in seconds
+///
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// test encode and decode methods
+    fn test_encode_decode() {
+        let entry: LogEntry = LogEntry::with_timestamp("key1", "value1", 1652000000);
+        let buf = entry.encode();
+
+        assert_eq!(buf.len() as u64, entry.size());
+        assert_eq!(buf.len(), HEADER_SIZE + 4 + 6);
+        assert_eq!(entry.value_offset(), (HEADER_SIZE + 4) as u64);
+
+        let decoded = LogEntry::decode(&buf).unwrap();
+
+        assert_eq!(decoded, entry);
+        assert_eq!(decoded.get_timestamp(), 1652000000);
+        assert_eq!(*decoded.get_key(), b"key1".to_vec());
+        assert_eq!(*decoded.get_value(), b"value1".to_vec());
+        assert!(!decoded.is_tombstone());
+        assert_eq!(decoded.get_expire(), 0);
+        assert_eq!(decoded.get_version(), 0);
+    }
+
+    #[test]
+    /// test versioned entries
+    fn test_version() {
+        let mut entry: LogEntry = LogEntry::new("key1", "value1");
+        let crc = entry.get_crc();
+
+        entry.set_version(7);
+
+        assert_ne!(entry.get_crc(), crc);
+
+        let decoded = LogEntry::decode(&entry.encode()).unwrap();
+
+        assert_eq!(decoded.get_version(), 7);
+        assert_eq!(decoded, entry);
+    }
+
+    #[test]
+    /// test expiring entries
+    fn test_expire() {
+        let entry: LogEntry = LogEntry::with_expire("key1", "value1", 1652000000);
+        let decoded = LogEntry::decode(&entry.encode()).unwrap();
+
+        assert_eq!(decoded.get_expire(), 1652000000);
+        assert!(decoded.is_expired(1652000000));
+        assert!(!decoded.is_expired(1651999999));
+        assert!(!LogEntry::new("key1", "value1").is_expired(now()));
+        assert_eq!(expire_at(0), 0);
+        assert!(expire_at(10) >= now() + 10);
+    }
+
+    #[test]
+    /// test tombstone entries
+    fn test_tombstone() {
+        let entry: LogEntry = LogEntry::tombstone("key1");
+        let decoded = LogEntry::decode(&entry.encode()).unwrap();
+
+        assert!(decoded.is_tombstone());
+        assert_eq!(decoded.get_value_size(), TOMBSTONE);
+        assert_eq!(*decoded.get_value(), Vec::<u8>::new());
+    }
+
+    #[test]
+    /// test corrupted and truncated entries
+    fn test_decode_errors() {
+        let entry: LogEntry = LogEntry::new("key1", "value1");
+        let mut buf = entry.encode();
+
+        assert!(LogEntry::decode(&buf[0..buf.len() - 1]).is_err());
+
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+
+        assert_eq!(
+            LogEntry::decode(&buf),
+            Err(Error::Corruption("entry CRC mismatch".to_string()))
+        );
+    }
+
+    #[test]
+    /// test batch records
+    fn test_batch() {
+        let entries = vec![LogEntry::new("key1", "value1"), LogEntry::tombstone("key2")];
+        let mut buf = encode_batch(&entries);
+
+        assert_eq!(
+            buf.len() as u64,
+            HEADER_SIZE as u64 + entries[0].size() + entries[1].size()
+        );
+        assert_eq!(
+            read_record(&mut buf.as_slice()),
+            Ok(Some(Record::Batch(entries)))
+        );
+        assert!(read_record(&mut &buf[0..buf.len() - 1]).is_err());
+
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+
+        assert!(read_record_unverified(&mut buf.as_slice()).is_ok());
+        assert_eq!(
+            read_record(&mut buf.as_slice()),
+            Err(Error::Corruption("batch CRC mismatch".to_string()))
+        );
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone)]
+// Error type
+pub enum Error {
+    // A file or a socket operation failed
+    Io(String),
+    // A client sent a request that can not be accepted
+    Protocol(String),
+    // A datafile or a hint file holds a truncated or corrupted record
+    Corruption(String),
+    // A key or a snapshot does not exist
+    NotFound(String),
+    // A setting is invalid
+    Config(String),
+    // A request conflicts with the current state, like an existing snapshot
+    State(String),
+}
+
+// Error type methods
+impl Error {
+    ///
+    /// Gets the error message
+    ///
+    pub fn message(&self) -> &str {
+        match self {
+            Error::Io(message)
+            | Error::Protocol(message)
+            | Error::Corruption(message)
+            | Error::NotFound(message)
+            | Error::Config(message)
+            | Error::State(message) => message.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(message) => write!(f, "Error raised: {}", message),
+            Error::Corruption(message) => write!(f, "Corrupted data: {}", message),
+            Error::Config(message) => write!(f, "Invalid config: {}", message),
+            Error::Protocol(message)
+            | Error::NotFound(message)
+            | Error::State(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e.to_string())
+    }
+}
+
+#[test]
+fn test_error_display() {
+    let e: Error = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+
+    assert_eq!(e, Error::Io("entity not found".to_string()));
+    assert_eq!(e.to_string(), "Error raised: entity not found");
+    assert_eq!(
+        Error::Corruption("entry CRC mismatch".to_string()).to_string(),
+        "Corrupted data: entry CRC mismatch"
+    );
+    assert_eq!(
+        Error::Config("MAX_DATAFILE_SIZE must be a number".to_string()).to_string(),
+        "Invalid config: MAX_DATAFILE_SIZE must be a number"
+    );
+    assert_eq!(
+        Error::NotFound("Snapshot `backup` not found".to_string()).message(),
+        "Snapshot `backup` not found"
+    );
+    assert_eq!(
+        Error::State("Snapshot `backup` already exists".to_string()).to_string(),
+        "Snapshot `backup` already exists"
+    );
+}
@@ -0,0 +1,774 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::command::{cursor, Command, Type};
+use crate::module::compact::Compact;
+use crate::module::database::{Database, Operation};
+use crate::module::entry::{expire_at, now};
+use crate::module::error::Error;
+use crate::module::namespace::SharedDatabase;
+use crate::module::response::{Code, Response};
+use crate::module::snapshot::Snapshot;
+use crate::module::transaction::Transaction;
+use crate::util::lock::{read_lock, write_lock};
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+///
+/// Executes a command against the database and returns the reply
+///
+/// # Arguments
+///
+/// * `db` - The shared database
+/// * `cmd` - The parsed command
+///
+/// # Returns
+///
+/// * The reply to write back to the client
+///
+pub fn handle(db: &SharedDatabase, cmd: &Command) -> Response {
+    match *cmd.get_name() {
+        Type::Ping => Response::Status("PONG".to_string()),
+        Type::Exit => Response::ok(),
+        Type::Get => {
+            let db = read_lock(db);
+
+            match db.get(cmd.get_key().as_slice()) {
+                Ok(Some(value)) => Response::Value(value),
+                Ok(None) => Response::Nil,
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Set => {
+            let mut db = write_lock(db);
+
+            match db.set_with_ttl(
+                cmd.get_key().as_slice(),
+                cmd.get_value().as_slice(),
+                *cmd.get_expire() as u64,
+            ) {
+                Ok(_) => Response::ok(),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Update => {
+            let mut db = write_lock(db);
+
+            if !db.exists(cmd.get_key().as_slice()) {
+                return Response::not_found();
+            }
+
+            match db.set_with_ttl(
+                cmd.get_key().as_slice(),
+                cmd.get_value().as_slice(),
+                *cmd.get_expire() as u64,
+            ) {
+                Ok(_) => Response::ok(),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Delete => {
+            let mut db = write_lock(db);
+
+            if !db.exists(cmd.get_key().as_slice()) {
+                return Response::not_found();
+            }
+
+            match db.remove(cmd.get_key().as_slice()) {
+                Ok(_) => Response::ok(),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Ttl => match read_lock(db).ttl(cmd.get_key().as_slice()) {
+            Some(ttl) => Response::Integer(ttl),
+            None => Response::Nil,
+        },
+        Type::Expire => {
+            let mut db = write_lock(db);
+
+            match db.expire(cmd.get_key().as_slice(), *cmd.get_expire() as u64) {
+                Ok(true) => Response::ok(),
+                Ok(false) => Response::not_found(),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Persist => {
+            let mut db = write_lock(db);
+
+            match db.persist(cmd.get_key().as_slice()) {
+                Ok(true) => Response::ok(),
+                Ok(false) => Response::not_found(),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Exists => {
+            let db = read_lock(db);
+
+            Response::Integer(db.exists(cmd.get_key().as_slice()) as i64)
+        }
+        Type::MGet => {
+            let db = read_lock(db);
+
+            Response::Array(
+                cmd.get_args()
+                    .iter()
+                    .map(|key| match db.get(key) {
+                        Ok(Some(value)) => Response::Value(value),
+                        Ok(None) => Response::Nil,
+                        Err(e) => Response::from(e),
+                    })
+                    .collect(),
+            )
+        }
+        Type::MSet => {
+            let mut db = write_lock(db);
+            let operations: Vec<Operation> = cmd
+                .get_args()
+                .chunks(2)
+                .map(|pair| Operation::Set(pair[0].to_vec(), pair[1].to_vec(), 0))
+                .collect();
+            let count = operations.len();
+
+            match db.apply(operations) {
+                Ok(_) => Response::Array(vec![Response::ok(); count]),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::MDelete => {
+            let mut db = write_lock(db);
+            let mut removed: HashSet<&Vec<u8>> = HashSet::new();
+            let mut replies: Vec<Response> = Vec::new();
+
+            // A key repeated in the request is only removed once
+            for key in cmd.get_args().iter() {
+                if db.exists(key) && removed.insert(key) {
+                    replies.push(Response::ok());
+                } else {
+                    replies.push(Response::not_found());
+                }
+            }
+
+            let operations: Vec<Operation> = removed
+                .into_iter()
+                .map(|key| Operation::Delete(key.to_vec()))
+                .collect();
+
+            match db.apply(operations) {
+                Ok(_) => Response::Array(replies),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::GetV => {
+            let db = read_lock(db);
+
+            match db.get_versioned(cmd.get_key().as_slice()) {
+                Ok(Some((value, version))) => Response::Array(vec![
+                    Response::Value(value),
+                    Response::Integer(version as i64),
+                ]),
+                Ok(None) => Response::Nil,
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::SetNx | Type::SetV => {
+            let mut db = write_lock(db);
+
+            // `SETNX` expects the key to be missing, that is at version zero
+            written(db.set_if_version(
+                cmd.get_key().as_slice(),
+                cmd.get_value().as_slice(),
+                expire_at(*cmd.get_expire() as u64),
+                cmd.get_version(),
+            ))
+        }
+        Type::Cas => {
+            let mut db = write_lock(db);
+
+            let expected = match db.get_versioned(cmd.get_key().as_slice()) {
+                Ok(Some((value, version))) if cmd.get_args().first() == Some(&value) => {
+                    version
+                }
+                Ok(_) => return Response::Integer(0),
+                Err(e) => return Response::from(e),
+            };
+
+            written(db.set_if_version(
+                cmd.get_key().as_slice(),
+                cmd.get_value().as_slice(),
+                expire_at(*cmd.get_expire() as u64),
+                expected,
+            ))
+        }
+        Type::Scan => {
+            let db = read_lock(db);
+            let (keys, next) = db.scan(
+                cmd.get_key(),
+                cmd.get_number(1) as usize,
+                &cmd.get_args()[0],
+            );
+
+            Response::Array(vec![
+                Response::Value(cursor(next.as_deref()).into_bytes()),
+                Response::Array(keys.into_iter().map(Response::Value).collect()),
+            ])
+        }
+        Type::Keys => {
+            let db = read_lock(db);
+
+            Response::Array(
+                db.keys(cmd.get_key())
+                    .into_iter()
+                    .map(Response::Value)
+                    .collect(),
+            )
+        }
+        Type::Range | Type::RevRange => {
+            let db = read_lock(db);
+            let limit = match cmd.get_number(1) {
+                0 => usize::MAX,
+                limit => limit as usize,
+            };
+
+            match db.range(
+                cmd.get_key(),
+                &cmd.get_args()[0],
+                limit,
+                *cmd.get_name() == Type::RevRange,
+            ) {
+                Ok(pairs) => Response::Array(
+                    pairs
+                        .into_iter()
+                        .flat_map(|(key, value)| {
+                            [Response::Value(key), Response::Value(value)]
+                        })
+                        .collect(),
+                ),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Append => {
+            let mut db = write_lock(db);
+
+            match db.append_value(cmd.get_key().as_slice(), cmd.get_value().as_slice()) {
+                Ok(len) => Response::Integer(len as i64),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::StrLen => {
+            let db = read_lock(db);
+
+            Response::Integer(db.strlen(cmd.get_key()) as i64)
+        }
+        Type::GetRange => {
+            let db = read_lock(db);
+            let offset = |i: usize| {
+                String::from_utf8_lossy(&cmd.get_args()[i])
+                    .parse::<i64>()
+                    .unwrap_or_default()
+            };
+
+            match db.get_range(cmd.get_key(), offset(0), offset(1)) {
+                Ok(value) => Response::Value(value),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Incr | Type::Decr | Type::IncrBy | Type::DecrBy => {
+            let mut db = write_lock(db);
+            let amount = String::from_utf8_lossy(cmd.get_value())
+                .parse::<i64>()
+                .unwrap_or_default();
+
+            match db.increment(cmd.get_key().as_slice(), amount) {
+                Ok(Some(value)) => Response::Integer(value),
+                Ok(None) => Response::error(
+                    Code::Value,
+                    "Value is not an integer or out of range",
+                ),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Stats => {
+            let db = read_lock(db);
+
+            Response::Status(format!(
+                "keys:{} expired_keys:{} total_bytes:{} dead_bytes:{}",
+                db.len(),
+                db.get_expired_keys(),
+                db.get_total_bytes(),
+                db.get_dead_bytes()
+            ))
+        }
+        Type::Compact => match Compact::start(db) {
+            Ok(Some(plan)) => {
+                let db = Arc::clone(db);
+
+                // The merge goes on in the background, the reply only says
+                // the compaction started
+                thread::spawn(move || {
+                    if let Err(e) = Compact::finish(&db, plan) {
+                        eprintln!(
+                            "Compaction of {} failed: {}",
+                            read_lock(&db).get_path(),
+                            e
+                        );
+                    }
+                });
+
+                Response::ok()
+            }
+            Ok(None) => Response::ok(),
+            Err(e) => Response::from(e),
+        },
+        Type::Snapshot => {
+            let name = match String::from_utf8_lossy(cmd.get_key()).as_ref() {
+                "" => format!("snapshot-{}", now()),
+                name => name.to_string(),
+            };
+
+            match snapshots(db).create(name.as_str(), db) {
+                Ok(_) => Response::Value(name.into_bytes()),
+                Err(e) => Response::from(e),
+            }
+        }
+        Type::Snapshots => match snapshots(db).list() {
+            Ok(names) => Response::Array(
+                names
+                    .into_iter()
+                    .map(|name| Response::Value(name.into_bytes()))
+                    .collect(),
+            ),
+            Err(e) => Response::from(e),
+        },
+        Type::Restore => match snapshots(db)
+            .restore(String::from_utf8_lossy(cmd.get_key()).as_ref(), db)
+        {
+            Ok(_) => Response::ok(),
+            Err(e) => Response::from(e),
+        },
+        _ => Response::error(
+            Code::State,
+            format!("{:?} is not allowed here", cmd.get_name()),
+        ),
+    }
+}
+
+#[derive(Debug, Default)]
+// Session type
+pub struct Session {
+    // The transaction opened by MULTI
+    transaction: Option<Transaction>,
+}
+
+// Session type methods
+impl Session {
+    ///
+    /// Returns the state of a new client connection
+    ///
+    pub fn new() -> Session {
+        Session { transaction: None }
+    }
+
+    ///
+    /// Whether a transaction is open
+    ///
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    ///
+    /// Executes a command within the connection state and returns the reply
+    ///
+    /// Write commands sent after `MULTI` are queued until `EXEC` applies
+    /// them atomically or `DISCARD` drops them. Writes that can not be
+    /// queued reply `ERR_STATE` and reads run at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    /// * `cmd` - The parsed command
+    ///
+    /// # Returns
+    ///
+    /// * The reply to write back to the client
+    ///
+    pub fn handle(&mut self, db: &SharedDatabase, cmd: &Command) -> Response {
+        match (cmd.get_name(), self.transaction.as_mut()) {
+            (Type::Multi, Some(_)) => {
+                Response::error(Code::State, "MULTI calls can not be nested")
+            }
+            (Type::Multi, None) => {
+                self.transaction = Some(Transaction::new());
+                Response::ok()
+            }
+            (Type::Exec, None) => Response::error(Code::State, "EXEC without MULTI"),
+            (Type::Discard, None) => {
+                Response::error(Code::State, "DISCARD without MULTI")
+            }
+            (Type::Exec, Some(_)) => match self.transaction.take() {
+                Some(transaction) => transaction.exec(db),
+                None => Response::error(Code::State, "EXEC without MULTI"),
+            },
+            (Type::Discard, Some(_)) => {
+                self.transaction = None;
+                Response::ok()
+            }
+            (_, Some(transaction)) if Transaction::accepts(cmd) => {
+                transaction.queue(cmd.clone());
+                Response::Status("QUEUED".to_string())
+            }
+            (_, Some(_)) if Transaction::rejects(cmd) => Response::error(
+                Code::State,
+                format!("{:?} is not allowed in a transaction", cmd.get_name()),
+            ),
+            _ => handle(db, cmd),
+        }
+    }
+}
+
+// The reply of a conditional write, its new version or zero if it was refused
+fn written(result: Result<Option<u64>, Error>) -> Response {
+    match result {
+        Ok(version) => Response::Integer(version.unwrap_or(0) as i64),
+        Err(e) => Response::from(e),
+    }
+}
+
+// The snapshots of the database are kept inside its directory
+fn snapshots(db: &RwLock<Database>) -> Snapshot {
+    Snapshot::new(format!("{}/snapshots", read_lock(db).get_path()))
+}
+
+#[test]
+fn test_handle() {
+    let _ = std::fs::remove_dir_all("./cache/handler_handle");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_handle")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+    let value = |value: &str| Response::Value(value.as_bytes().to_vec());
+
+    assert_eq!(run("PING"), Response::Status("PONG".to_string()));
+    assert_eq!(run("GET item1"), Response::Nil);
+    assert_eq!(run("UPDATE item1 value1"), Response::not_found());
+    assert_eq!(run("SET item1 value1"), Response::ok());
+    assert_eq!(run("GET item1"), value("value1"));
+    assert_eq!(run("EXISTS item1"), Response::Integer(1));
+    assert_eq!(run("EXISTS item9"), Response::Integer(0));
+    assert_eq!(run("UPDATE item1 value2"), Response::ok());
+    assert_eq!(run("GET item1"), value("value2"));
+    assert_eq!(run("DELETE item1"), Response::ok());
+    assert_eq!(run("DELETE item1"), Response::not_found());
+    assert_eq!(run("GET item1"), Response::Nil);
+    assert_eq!(run("SET item1 \"\""), Response::ok());
+    assert_eq!(run("GET item1"), value(""));
+    assert_eq!(run("COMPACT"), Response::ok());
+    assert_eq!(run("SET item2 value1"), Response::ok());
+    assert_eq!(run("SNAPSHOT backup"), value("backup"));
+    assert_eq!(run("SNAPSHOTS"), Response::Array(vec![value("backup")]));
+    assert_eq!(
+        run("SNAPSHOT backup"),
+        Response::error(Code::State, "Snapshot `backup` already exists")
+    );
+    assert_eq!(run("DELETE item2"), Response::ok());
+    assert_eq!(run("RESTORE backup"), Response::ok());
+    assert_eq!(run("GET item2"), value("value1"));
+    assert_eq!(
+        run("RESTORE missing"),
+        Response::error(Code::NotFound, "Snapshot `missing` not found")
+    );
+    assert_eq!(run("EXIT"), Response::ok());
+    assert_eq!(
+        run("EXEC"),
+        Response::error(Code::State, "Exec is not allowed here")
+    );
+}
+
+#[test]
+fn test_handle_multi() {
+    let _ = std::fs::remove_dir_all("./cache/handler_multi");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_multi")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+    let value = |value: &str| Response::Value(value.as_bytes().to_vec());
+
+    assert_eq!(
+        run("MSET item1 value1 item2 value2 item1 value3"),
+        Response::Array(vec![Response::ok(); 3])
+    );
+    assert_eq!(
+        run("MGET item1 item2 item3"),
+        Response::Array(vec![value("value3"), value("value2"), Response::Nil])
+    );
+    assert_eq!(
+        run("MDELETE item1 item3 item1"),
+        Response::Array(vec![
+            Response::ok(),
+            Response::not_found(),
+            Response::not_found()
+        ])
+    );
+    assert_eq!(
+        run("MGET item1 item2"),
+        Response::Array(vec![Response::Nil, value("value2")])
+    );
+
+    // The pairs are written as a single batch record
+    let db = db.read().unwrap();
+
+    assert_eq!(db.datafile_ids(), Ok(vec![1]));
+    assert_eq!(db.len(), 1);
+}
+
+#[test]
+fn test_handle_conditional() {
+    let _ = std::fs::remove_dir_all("./cache/handler_conditional");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_conditional")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+    let versioned = |value: &str, version: i64| {
+        Response::Array(vec![
+            Response::Value(value.as_bytes().to_vec()),
+            Response::Integer(version),
+        ])
+    };
+
+    assert_eq!(run("GETV item1"), Response::Nil);
+    assert_eq!(run("SETNX item1 value1"), Response::Integer(1));
+    assert_eq!(run("SETNX item1 value2"), Response::Integer(0));
+    assert_eq!(run("GETV item1"), versioned("value1", 1));
+    assert_eq!(run("CAS item1 value2 value3"), Response::Integer(0));
+    assert_eq!(run("CAS item1 value1 value3"), Response::Integer(2));
+    assert_eq!(run("CAS item9 value1 value3"), Response::Integer(0));
+    assert_eq!(run("SETV item1 1 value4"), Response::Integer(0));
+    assert_eq!(run("SETV item1 2 value4"), Response::Integer(3));
+    assert_eq!(run("SET item1 value5"), Response::ok());
+    assert_eq!(run("GETV item1"), versioned("value5", 4));
+    assert_eq!(run("SETV item2 0 value1 100"), Response::Integer(5));
+    assert_ne!(run("TTL item2"), Response::Integer(-1));
+}
+
+#[test]
+fn test_handle_scan() {
+    let _ = std::fs::remove_dir_all("./cache/handler_scan");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_scan")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+    let value = |value: &str| Response::Value(value.as_bytes().to_vec());
+
+    assert_eq!(
+        run("MSET user:1 a user:2 b order:1 c"),
+        Response::Array(vec![Response::ok(); 3])
+    );
+    assert_eq!(
+        run("SCAN 0 COUNT 2"),
+        Response::Array(vec![
+            value(&cursor(Some(b"user:2"))),
+            Response::Array(vec![value("order:1"), value("user:1")]),
+        ])
+    );
+    assert_eq!(
+        run(&format!("SCAN {} COUNT 2", cursor(Some(b"user:2")))),
+        Response::Array(vec![value("0"), Response::Array(vec![value("user:2")])])
+    );
+    assert_eq!(
+        run("SCAN 0 MATCH order:*"),
+        Response::Array(vec![value("0"), Response::Array(vec![value("order:1")])])
+    );
+    assert_eq!(
+        run("KEYS user:"),
+        Response::Array(vec![value("user:1"), value("user:2")])
+    );
+    assert_eq!(run("KEYS item"), Response::Array(Vec::new()));
+    assert_eq!(
+        run("RANGE user:1 user:9"),
+        Response::Array(vec![
+            value("user:1"),
+            value("a"),
+            value("user:2"),
+            value("b")
+        ])
+    );
+    assert_eq!(
+        run("REVRANGE order:1 user:9 LIMIT 2"),
+        Response::Array(vec![
+            value("user:2"),
+            value("b"),
+            value("user:1"),
+            value("a")
+        ])
+    );
+}
+
+#[test]
+fn test_handle_compact() {
+    let _ = std::fs::remove_dir_all("./cache/handler_compact");
+
+    let mut database: Database = Database::new("./cache/handler_compact");
+    database.set_max_file_size(92);
+
+    let db = Arc::new(RwLock::new(database));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+
+    for cmd in ["SET item1 value1", "SET item2 value2", "SET item1 value3"] {
+        assert_eq!(run(cmd), Response::ok());
+    }
+
+    // The merge goes on in the background once the reply is sent
+    assert_eq!(run("COMPACT"), Response::ok());
+
+    while read_lock(&db).is_compacting() {
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    assert_eq!(run("GET item1"), Response::Value(b"value3".to_vec()));
+    assert_eq!(run("GET item2"), Response::Value(b"value2".to_vec()));
+    assert_eq!(read_lock(&db).get_dead_bytes(), 0);
+}
+
+#[test]
+fn test_handle_counter() {
+    let _ = std::fs::remove_dir_all("./cache/handler_counter");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_counter")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+
+    assert_eq!(run("INCR hits"), Response::Integer(1));
+    assert_eq!(run("INCRBY hits 10"), Response::Integer(11));
+    assert_eq!(run("DECR hits"), Response::Integer(10));
+    assert_eq!(run("DECRBY hits 20"), Response::Integer(-10));
+    assert_eq!(run("GET hits"), Response::Value(b"-10".to_vec()));
+    assert_eq!(run("SET name langmore"), Response::ok());
+    assert_eq!(
+        run("INCR name"),
+        Response::error(Code::Value, "Value is not an integer or out of range")
+    );
+}
+
+#[test]
+fn test_handle_append() {
+    let _ = std::fs::remove_dir_all("./cache/handler_append");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_append")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+
+    assert_eq!(run("APPEND log first"), Response::Integer(5));
+    assert_eq!(run("APPEND log \",second\""), Response::Integer(12));
+    assert_eq!(run("STRLEN log"), Response::Integer(12));
+    assert_eq!(run("STRLEN missing"), Response::Integer(0));
+    assert_eq!(
+        run("GETRANGE log 6 -1"),
+        Response::Value(b"second".to_vec())
+    );
+    assert_eq!(run("GETRANGE missing 0 -1"), Response::Value(Vec::new()));
+}
+
+#[test]
+fn test_handle_expire() {
+    let _ = std::fs::remove_dir_all("./cache/handler_expire");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_expire")));
+    let run = |cmd: &str| handle(&db, &Command::from_str(cmd).unwrap());
+
+    assert_eq!(run("TTL item1"), Response::Nil);
+    assert_eq!(run("SET item1 value1"), Response::ok());
+    assert_eq!(run("TTL item1"), Response::Integer(-1));
+    assert_eq!(run("EXPIRE item1 100"), Response::ok());
+    assert_ne!(run("TTL item1"), Response::Integer(-1));
+    assert_eq!(run("PERSIST item1"), Response::ok());
+    assert_eq!(run("TTL item1"), Response::Integer(-1));
+    assert_eq!(run("SET item2 value2 100"), Response::ok());
+    assert_ne!(run("TTL item2"), Response::Integer(-1));
+    assert_eq!(run("EXPIRE item2 0"), Response::ok());
+    assert_eq!(run("GET item2"), Response::Nil);
+    assert_eq!(run("EXPIRE item2 10"), Response::not_found());
+    assert_eq!(run("PERSIST item2"), Response::not_found());
+
+    match run("STATS") {
+        Response::Status(stats) => assert!(stats.starts_with("keys:1 expired_keys:0 ")),
+        response => panic!("Unexpected response {:?}", response),
+    }
+}
+
+#[test]
+fn test_session() {
+    let _ = std::fs::remove_dir_all("./cache/handler_session");
+
+    let db = Arc::new(RwLock::new(Database::new("./cache/handler_session")));
+    let mut session: Session = Session::new();
+    let mut run = |cmd: &str| session.handle(&db, &Command::from_str(cmd).unwrap());
+    let queued = || Response::Status("QUEUED".to_string());
+
+    assert_eq!(
+        run("EXEC"),
+        Response::error(Code::State, "EXEC without MULTI")
+    );
+    assert_eq!(
+        run("DISCARD"),
+        Response::error(Code::State, "DISCARD without MULTI")
+    );
+    assert_eq!(run("MULTI"), Response::ok());
+    assert_eq!(
+        run("MULTI"),
+        Response::error(Code::State, "MULTI calls can not be nested")
+    );
+    assert_eq!(run("SET item1 value1"), queued());
+    assert_eq!(run("GET item1"), Response::Nil);
+    assert_eq!(run("DISCARD"), Response::ok());
+    assert_eq!(run("GET item1"), Response::Nil);
+
+    assert_eq!(run("MULTI"), Response::ok());
+    assert_eq!(run("SET item1 value1"), queued());
+    assert_eq!(run("SET item2 value2"), queued());
+    assert_eq!(run("DELETE item3"), queued());
+    assert_eq!(
+        run("EXEC"),
+        Response::Array(vec![Response::ok(), Response::ok(), Response::not_found()])
+    );
+    assert_eq!(run("GET item2"), Response::Value(b"value2".to_vec()));
+
+    assert_eq!(run("MULTI"), Response::ok());
+    assert_eq!(run("MSET item1 value3 item4 value4"), queued());
+    assert_eq!(run("MDELETE item2"), queued());
+    assert_eq!(
+        run("INCR item5"),
+        Response::error(Code::State, "Incr is not allowed in a transaction")
+    );
+    assert_eq!(
+        run("SETNX item5 value5"),
+        Response::error(Code::State, "SetNx is not allowed in a transaction")
+    );
+    assert_eq!(
+        run("SNAPSHOT backup"),
+        Response::error(Code::State, "Snapshot is not allowed in a transaction")
+    );
+    assert_eq!(
+        run("RESTORE backup"),
+        Response::error(Code::State, "Restore is not allowed in a transaction")
+    );
+    assert_eq!(
+        run("COMPACT"),
+        Response::error(Code::State, "Compact is not allowed in a transaction")
+    );
+    assert_eq!(run("DISCARD"), Response::ok());
+    assert_eq!(run("GET item1"), Response::Value(b"value1".to_vec()));
+    assert_eq!(run("GET item2"), Response::Value(b"value2".to_vec()));
+    assert_eq!(run("GET item4"), Response::Nil);
+    assert_eq!(run("GET item5"), Response::Nil);
+
+    assert_eq!(run("MULTI"), Response::ok());
+    assert_eq!(run("MSET item1 value3 item4 value4"), queued());
+    assert_eq!(run("MDELETE item2 item4 item6"), queued());
+    assert_eq!(
+        run("EXEC"),
+        Response::Array(vec![
+            Response::Array(vec![Response::ok(), Response::ok()]),
+            Response::Array(vec![Response::ok(), Response::ok(), Response::not_found()]),
+        ])
+    );
+    assert_eq!(run("GET item1"), Response::Value(b"value3".to_vec()));
+    assert_eq!(run("GET item2"), Response::Nil);
+    assert_eq!(run("GET item4"), Response::Nil);
+    assert!(!session.in_transaction());
+}
@@ -0,0 +1,340 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::entry::TOMBSTONE;
+use crate::module::error::Error;
+use crate::module::reader::Reader;
+
+use crc32fast::Hasher;
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{read, rename, File};
+use std::io::Write;
+
+/// The size of the hint entry header: timestamp, expire, version, key size, value
+/// size and value position
+pub const HINT_HEADER_SIZE: usize = 40;
+
+#[derive(Debug, PartialEq, Clone)]
+// HintEntry type
+pub struct HintEntry {
+    // The entry unix timestamp
+    timestamp: u64,
+    // The unix timestamp the value expires at or zero
+    expire: u64,
+    // The entry version
+    version: u64,
+    // The key size in bytes
+    key_size: u32,
+    // The value size in bytes or TOMBSTONE
+    value_size: u32,
+    // The value offset in the datafile
+    value_pos: u64,
+    // The actual key
+    key: Vec<u8>,
+}
+
+// HintEntry type methods
+impl HintEntry {
+    ///
+    /// Returns a hint entry pointing to a value in the companion datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `timestamp` - The entry unix timestamp
+    /// * `expire` - The unix timestamp the value expires at or zero
+    /// * `version` - The entry version
+    /// * `value_size` - The value size in bytes or TOMBSTONE
+    /// * `value_pos` - The value offset in the datafile
+    /// * `key` - The entry key
+    ///
+    pub fn new<S: Into<Vec<u8>>>(
+        timestamp: u64,
+        expire: u64,
+        version: u64,
+        value_size: u32,
+        value_pos: u64,
+        key: S,
+    ) -> HintEntry {
+        let key = key.into();
+
+        HintEntry {
+            timestamp,
+            expire,
+            version,
+            key_size: key.len() as u32,
+            value_size,
+            value_pos,
+            key,
+        }
+    }
+
+    pub fn get_timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn get_expire(&self) -> u64 {
+        self.expire
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn get_key_size(&self) -> u32 {
+        self.key_size
+    }
+
+    pub fn get_value_size(&self) -> u32 {
+        self.value_size
+    }
+
+    pub fn get_value_pos(&self) -> u64 {
+        self.value_pos
+    }
+
+    pub fn get_key(&self) -> &Vec<u8> {
+        &self.key
+    }
+
+    ///
+    /// Whether the hint marks its key as deleted
+    ///
+    pub fn is_tombstone(&self) -> bool {
+        self.value_size == TOMBSTONE
+    }
+
+    ///
+    /// Encodes the hint as
+    /// `timestamp | expire | version | key_size | value_size | value_pos | key`
+    ///
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(HINT_HEADER_SIZE + self.key.len());
+
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(&self.expire.to_be_bytes());
+        buf.extend_from_slice(&self.version.to_be_bytes());
+        buf.extend_from_slice(&self.key_size.to_be_bytes());
+        buf.extend_from_slice(&self.value_size.to_be_bytes());
+        buf.extend_from_slice(&self.value_pos.to_be_bytes());
+        buf.extend_from_slice(&self.key);
+        buf
+    }
+}
+
+// Hint type
+pub struct Hint {
+    // The path to the hint file
+    path: String,
+}
+
+// Hint type methods
+impl Hint {
+    ///
+    /// Returns a hint object for the hint file path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the hint file
+    ///
+    pub fn new<S: Into<String>>(path: S) -> Hint {
+        Hint { path: path.into() }
+    }
+
+    ///
+    /// Builds the hint entries of a datafile, keeping the last entry of each key
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the datafile
+    ///
+    /// # Returns
+    ///
+    /// * The hint entries ordered by value position
+    /// * Error raised
+    ///
+    pub fn from_datafile(path: String) -> Result<Vec<HintEntry>, Error> {
+        let mut latest: HashMap<Vec<u8>, HintEntry> = HashMap::new();
+
+        // Like bootup, only the entries before a corrupted one are kept
+        for item in Reader::open(path)? {
+            let (offset, entry) = match item {
+                Ok(item) => item,
+                Err(_) => break,
+            };
+
+            latest.insert(
+                entry.get_key().to_vec(),
+                HintEntry::new(
+                    entry.get_timestamp(),
+                    entry.get_expire(),
+                    entry.get_version(),
+                    entry.get_value_size(),
+                    offset + entry.value_offset(),
+                    entry.get_key().to_vec(),
+                ),
+            );
+        }
+
+        let mut entries: Vec<HintEntry> = latest.into_values().collect();
+
+        entries.sort_by_key(|entry| entry.get_value_pos());
+
+        Ok(entries)
+    }
+
+    ///
+    /// Writes the hint file followed by a CRC32 of its content
+    ///
+    /// The content goes to a temporary file first which is then renamed
+    /// so a crash never leaves a partial hint file behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The hint entries
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn write(&self, entries: &[HintEntry]) -> Result<(), Error> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        for entry in entries.iter() {
+            buf.extend_from_slice(&entry.encode());
+        }
+
+        let mut hasher = Hasher::new();
+        hasher.update(&buf);
+        buf.extend_from_slice(&hasher.finalize().to_be_bytes());
+
+        let tmp = format!("{}.tmp", self.path);
+
+        File::create(tmp.as_str())
+            .and_then(|mut f| {
+                f.write_all(&buf)?;
+                f.sync_all()
+            })
+            .and_then(|_| rename(tmp.as_str(), self.path.as_str()))
+            .map_err(Error::from)
+    }
+
+    ///
+    /// Reads the hint file
+    ///
+    /// # Returns
+    ///
+    /// * The hint entries
+    /// * Error raised if the file is missing, truncated or the CRC mismatches
+    ///
+    pub fn read(&self) -> Result<Vec<HintEntry>, Error> {
+        let buf = read(self.path.as_str())?;
+
+        if buf.len() < 4 {
+            return Err(Error::Corruption("truncated hint file".to_string()));
+        }
+
+        let (content, crc) = buf.split_at(buf.len() - 4);
+        let mut hasher = Hasher::new();
+        hasher.update(content);
+
+        if hasher.finalize() != u32::from_be_bytes(crc.try_into().unwrap()) {
+            return Err(Error::Corruption("hint file CRC mismatch".to_string()));
+        }
+
+        let mut entries: Vec<HintEntry> = Vec::new();
+        let mut pos = 0;
+
+        while pos < content.len() {
+            if pos + HINT_HEADER_SIZE > content.len() {
+                return Err(Error::Corruption("truncated hint entry".to_string()));
+            }
+
+            let header = &content[pos..pos + HINT_HEADER_SIZE];
+            let timestamp = u64::from_be_bytes(header[0..8].try_into().unwrap());
+            let expire = u64::from_be_bytes(header[8..16].try_into().unwrap());
+            let version = u64::from_be_bytes(header[16..24].try_into().unwrap());
+            let key_size =
+                u32::from_be_bytes(header[24..28].try_into().unwrap()) as usize;
+            let value_size = u32::from_be_bytes(header[28..32].try_into().unwrap());
+            let value_pos = u64::from_be_bytes(header[32..40].try_into().unwrap());
+
+            pos += HINT_HEADER_SIZE;
+
+            if pos + key_size > content.len() {
+                return Err(Error::Corruption("truncated hint entry".to_string()));
+            }
+
+            entries.push(HintEntry::new(
+                timestamp,
+                expire,
+                version,
+                value_size,
+                value_pos,
+                &content[pos..pos + key_size],
+            ));
+
+            pos += key_size;
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::entry::{LogEntry, HEADER_SIZE};
+    use crate::module::writer::Writer;
+
+    #[test]
+    /// test write and read methods
+    fn test_write_read() {
+        let hint: Hint = Hint::new("cache/hint_write_read.hint");
+        let entries = vec![
+            HintEntry::new(1652000000, 1652000060, 3, 6, 32, "key1"),
+            HintEntry::new(1652000001, 0, 4, TOMBSTONE, 0, "key2"),
+        ];
+
+        assert_eq!(hint.write(&entries), Ok(()));
+        assert_eq!(hint.read(), Ok(entries));
+    }
+
+    #[test]
+    /// test reading a corrupted hint file
+    fn test_read_corrupted() {
+        let wt: Writer = Writer::new();
+        let hint: Hint = Hint::new("cache/hint_corrupted.hint");
+
+        let _ = hint.write(&[HintEntry::new(1652000000, 0, 1, 6, 32, "key1")]);
+        let _ = wt.append("cache/hint_corrupted.hint".to_string(), "x".to_string());
+
+        assert_eq!(
+            hint.read(),
+            Err(Error::Corruption("hint file CRC mismatch".to_string()))
+        );
+        assert!(Hint::new("cache/hint_missing.hint").read().is_err());
+    }
+
+    #[test]
+    /// test from_datafile method
+    fn test_from_datafile() {
+        let wt: Writer = Writer::new();
+        let path = "cache/hint_from_datafile.data".to_string();
+        let _ = wt.overwrite(path.to_string(), "".to_string());
+
+        let _ = wt.append_entry(path.to_string(), &LogEntry::new("key1", "value1"));
+        let _ = wt.append_entry(path.to_string(), &LogEntry::new("key2", "value2"));
+        let _ = wt.append_entry(path.to_string(), &LogEntry::tombstone("key1"));
+
+        let entries = Hint::from_datafile(path).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(*entries[0].get_key(), b"key2".to_vec());
+        assert_eq!(entries[0].get_value_pos(), (46 + HEADER_SIZE + 4) as u64);
+        assert_eq!(*entries[1].get_key(), b"key1".to_vec());
+        assert!(entries[1].is_tombstone());
+    }
+}
@@ -0,0 +1,631 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::entry::is_expired;
+use crate::module::error::Error;
+
+use rand::seq::index::sample;
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone)]
+// KeyDirEntry type
+pub struct KeyDirEntry {
+    // The datafile id holding the value
+    file_id: u32,
+    // The value size in bytes
+    value_sz: u32,
+    // The value offset in the datafile
+    value_pos: u64,
+    // The entry unix timestamp
+    tstamp: u64,
+    // The unix timestamp the value expires at or zero
+    expire: u64,
+    // The entry version
+    version: u64,
+}
+
+// KeyDirEntry type methods
+impl KeyDirEntry {
+    ///
+    /// Returns a keydir entry pointing to a value in a datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The datafile id
+    /// * `value_sz` - The value size in bytes
+    /// * `value_pos` - The value offset in the datafile
+    /// * `tstamp` - The entry unix timestamp
+    /// * `expire` - The unix timestamp the value expires at or zero
+    /// * `version` - The entry version
+    ///
+    pub fn new(
+        file_id: u32,
+        value_sz: u32,
+        value_pos: u64,
+        tstamp: u64,
+        expire: u64,
+        version: u64,
+    ) -> KeyDirEntry {
+        KeyDirEntry {
+            file_id,
+            value_sz,
+            value_pos,
+            tstamp,
+            expire,
+            version,
+        }
+    }
+
+    pub fn get_file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    pub fn get_value_sz(&self) -> u32 {
+        self.value_sz
+    }
+
+    pub fn get_value_pos(&self) -> u64 {
+        self.value_pos
+    }
+
+    pub fn get_tstamp(&self) -> u64 {
+        self.tstamp
+    }
+
+    pub fn get_expire(&self) -> u64 {
+        self.expire
+    }
+
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    ///
+    /// Whether the value expired at the provided time
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current unix timestamp
+    ///
+    pub fn is_expired(&self, now: u64) -> bool {
+        is_expired(self.expire, now)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+// IndexType type
+pub enum IndexType {
+    // A hash table, the fastest for point reads
+    #[default]
+    Hash,
+    // A B-tree keeping the keys sorted for range reads
+    BTree,
+}
+
+impl FromStr for IndexType {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "hash" => Ok(IndexType::Hash),
+            "btree" => Ok(IndexType::BTree),
+            _ => Err(Error::Config(format!("Unknown index type `{}`", value))),
+        }
+    }
+}
+
+#[derive(Debug)]
+// Index type
+enum Index {
+    Hash(HashMap<Vec<u8>, KeyDirEntry>),
+    BTree(BTreeMap<Vec<u8>, KeyDirEntry>),
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index::Hash(HashMap::new())
+    }
+}
+
+#[derive(Debug, Default)]
+// KeyDir type
+pub struct KeyDir {
+    // The key to value location map
+    entries: Index,
+    // The highest entry version seen, deleted keys included
+    version: u64,
+    // The keys with an expiry, sampled by the sweeper
+    expiring: Vec<Vec<u8>>,
+    // The position of each key with an expiry in `expiring`
+    expiring_pos: HashMap<Vec<u8>, usize>,
+}
+
+// KeyDir type methods
+impl KeyDir {
+    ///
+    /// Returns an empty keydir
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::keydir::KeyDir;
+    ///
+    /// let keydir: KeyDir = KeyDir::new();
+    /// ```
+    ///
+    pub fn new() -> KeyDir {
+        KeyDir::with_index(IndexType::Hash)
+    }
+
+    ///
+    /// Returns an empty keydir backed by the provided index
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index type
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::keydir::{IndexType, KeyDir};
+    ///
+    /// let keydir: KeyDir = KeyDir::with_index(IndexType::BTree);
+    /// ```
+    ///
+    pub fn with_index(index: IndexType) -> KeyDir {
+        let entries = match index {
+            IndexType::Hash => Index::Hash(HashMap::new()),
+            IndexType::BTree => Index::BTree(BTreeMap::new()),
+        };
+
+        KeyDir {
+            entries,
+            version: 0,
+            expiring: Vec::new(),
+            expiring_pos: HashMap::new(),
+        }
+    }
+
+    ///
+    /// Gets the index type backing the keydir
+    ///
+    pub fn get_index_type(&self) -> IndexType {
+        match self.entries {
+            Index::Hash(_) => IndexType::Hash,
+            Index::BTree(_) => IndexType::BTree,
+        }
+    }
+
+    ///
+    /// Stores or replaces the location of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    /// * `entry` - The value location
+    ///
+    /// # Returns
+    ///
+    /// * The previous location of the key if any
+    ///
+    pub fn put<K: Into<Vec<u8>>>(
+        &mut self,
+        key: K,
+        entry: KeyDirEntry,
+    ) -> Option<KeyDirEntry> {
+        let key = key.into();
+
+        self.bump_version(entry.get_version());
+
+        match entry.get_expire() {
+            0 => self.unmark_expiring(&key),
+            _ => self.mark_expiring(&key),
+        }
+
+        match &mut self.entries {
+            Index::Hash(entries) => entries.insert(key, entry),
+            Index::BTree(entries) => entries.insert(key, entry),
+        }
+    }
+
+    ///
+    /// Gets the location of a key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The value location if the key exists
+    ///
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Option<&KeyDirEntry> {
+        match &self.entries {
+            Index::Hash(entries) => entries.get(key.as_ref()),
+            Index::BTree(entries) => entries.get(key.as_ref()),
+        }
+    }
+
+    ///
+    /// Removes a key
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key bytes
+    ///
+    /// # Returns
+    ///
+    /// * The removed location if the key existed
+    ///
+    pub fn remove<K: AsRef<[u8]>>(&mut self, key: K) -> Option<KeyDirEntry> {
+        self.unmark_expiring(key.as_ref());
+
+        match &mut self.entries {
+            Index::Hash(entries) => entries.remove(key.as_ref()),
+            Index::BTree(entries) => entries.remove(key.as_ref()),
+        }
+    }
+
+    ///
+    /// Whether the key exists
+    ///
+    pub fn contains<K: AsRef<[u8]>>(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    ///
+    /// Gets the highest entry version seen
+    ///
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    ///
+    /// Raises the highest entry version seen
+    ///
+    /// Tombstones are not stored, their version is recorded here so the
+    /// versions of a deleted key are never handed out again.
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The version of an entry
+    ///
+    pub fn bump_version(&mut self, version: u64) {
+        self.version = self.version.max(version);
+    }
+
+    ///
+    /// The number of keys with an expiry
+    ///
+    pub fn expiring_len(&self) -> usize {
+        self.expiring.len()
+    }
+
+    ///
+    /// Picks random keys among the keys with an expiry
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The maximum number of keys to pick
+    ///
+    /// # Returns
+    ///
+    /// * The picked keys and their locations
+    ///
+    pub fn sample_expiring(&self, count: usize) -> Vec<(&Vec<u8>, &KeyDirEntry)> {
+        let count = count.min(self.expiring.len());
+
+        sample(&mut rand::thread_rng(), self.expiring.len(), count)
+            .into_iter()
+            .filter_map(|i| {
+                let key = &self.expiring[i];
+                self.get(key).map(|entry| (key, entry))
+            })
+            .collect()
+    }
+
+    ///
+    /// Gets the first keys in key order starting at the provided key
+    ///
+    /// A B-tree index walks the returned keys only, a hash index makes a
+    /// pass over every key and sorts the `limit` smallest ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The smallest key to return
+    /// * `limit` - The maximum number of keys to return
+    ///
+    /// # Returns
+    ///
+    /// * The keys and their locations in ascending key order
+    ///
+    pub fn scan<'a>(
+        &'a self,
+        start: &'a [u8],
+        limit: usize,
+    ) -> Vec<(&'a Vec<u8>, &'a KeyDirEntry)> {
+        match &self.entries {
+            Index::Hash(entries) => {
+                let mut keys: Vec<(&Vec<u8>, &KeyDirEntry)> = entries
+                    .iter()
+                    .filter(|(key, _)| key.as_slice() >= start)
+                    .collect();
+
+                if limit < keys.len() {
+                    keys.select_nth_unstable_by(limit, |a, b| a.0.cmp(b.0));
+                    keys.truncate(limit);
+                }
+
+                keys.sort_unstable_by(|a, b| a.0.cmp(b.0));
+                keys
+            }
+            Index::BTree(_) => self.range(start, None, false).take(limit).collect(),
+        }
+    }
+
+    ///
+    /// Iterates over the keys between two keys in key order
+    ///
+    /// A B-tree index walks the range only, a hash index collects and sorts
+    /// the keys of the range on every call.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The smallest key to return
+    /// * `end` - The largest key to return or None for no upper bound
+    /// * `reverse` - Whether to walk from the largest key down
+    ///
+    /// # Returns
+    ///
+    /// * The keys and their locations
+    ///
+    pub fn range<'a>(
+        &'a self,
+        start: &'a [u8],
+        end: Option<&'a [u8]>,
+        reverse: bool,
+    ) -> Box<dyn Iterator<Item = (&'a Vec<u8>, &'a KeyDirEntry)> + 'a> {
+        if end.is_some_and(|end| end < start) {
+            return Box::new(std::iter::empty());
+        }
+
+        let upper = match end {
+            Some(end) => Bound::Included(end),
+            None => Bound::Unbounded,
+        };
+
+        match &self.entries {
+            Index::BTree(entries) => {
+                let range = entries.range::<[u8], _>((Bound::Included(start), upper));
+
+                match reverse {
+                    true => Box::new(range.rev()),
+                    false => Box::new(range),
+                }
+            }
+            Index::Hash(entries) => {
+                let mut keys: Vec<(&Vec<u8>, &KeyDirEntry)> = entries
+                    .iter()
+                    .filter(|(key, _)| {
+                        key.as_slice() >= start
+                            && end.is_none_or(|end| key.as_slice() <= end)
+                    })
+                    .collect();
+
+                keys.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+                match reverse {
+                    true => Box::new(keys.into_iter().rev()),
+                    false => Box::new(keys.into_iter()),
+                }
+            }
+        }
+    }
+
+    ///
+    /// Iterates over the keys and their locations
+    ///
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&Vec<u8>, &KeyDirEntry)> + '_> {
+        match &self.entries {
+            Index::Hash(entries) => Box::new(entries.iter()),
+            Index::BTree(entries) => Box::new(entries.iter()),
+        }
+    }
+
+    ///
+    /// The number of keys
+    ///
+    pub fn len(&self) -> usize {
+        match &self.entries {
+            Index::Hash(entries) => entries.len(),
+            Index::BTree(entries) => entries.len(),
+        }
+    }
+
+    ///
+    /// Whether the keydir holds no keys
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Adds a key to the keys with an expiry
+    fn mark_expiring(&mut self, key: &[u8]) {
+        if !self.expiring_pos.contains_key(key) {
+            self.expiring_pos.insert(key.to_vec(), self.expiring.len());
+            self.expiring.push(key.to_vec());
+        }
+    }
+
+    // Removes a key from the keys with an expiry, the last one takes its place
+    fn unmark_expiring(&mut self, key: &[u8]) {
+        if let Some(pos) = self.expiring_pos.remove(key) {
+            self.expiring.swap_remove(pos);
+
+            if let Some(moved) = self.expiring.get(pos) {
+                self.expiring_pos.insert(moved.to_vec(), pos);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_keydir_methods() {
+    let mut keydir: KeyDir = KeyDir::new();
+
+    assert!(keydir.is_empty());
+    assert_eq!(
+        keydir.put("key1", KeyDirEntry::new(1, 6, 32, 1652000000, 0, 1)),
+        None
+    );
+    assert_eq!(
+        keydir.put(
+            "key1",
+            KeyDirEntry::new(2, 6, 70, 1652000001, 1652000060, 2)
+        ),
+        Some(KeyDirEntry::new(1, 6, 32, 1652000000, 0, 1))
+    );
+
+    let entry = keydir.get("key1").unwrap();
+
+    assert_eq!(entry.get_file_id(), 2);
+    assert_eq!(entry.get_value_sz(), 6);
+    assert_eq!(entry.get_value_pos(), 70);
+    assert_eq!(entry.get_tstamp(), 1652000001);
+    assert_eq!(entry.get_expire(), 1652000060);
+    assert_eq!(entry.get_version(), 2);
+    assert!(entry.is_expired(1652000060));
+    assert!(!entry.is_expired(1652000059));
+    assert!(keydir.contains("key1"));
+    assert_eq!(keydir.len(), 1);
+
+    assert!(keydir.remove("key1").is_some());
+    assert!(keydir.remove("key1").is_none());
+    assert!(!keydir.contains("key1"));
+
+    for key in ["key3", "key1", "key4", "key2"] {
+        keydir.put(key, KeyDirEntry::new(1, 6, 32, 1652000000, 0, 3));
+    }
+
+    let keys = |page: Vec<(&Vec<u8>, &KeyDirEntry)>| {
+        page.into_iter()
+            .map(|(key, _)| String::from_utf8_lossy(key).to_string())
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(keys(keydir.scan(b"", 2)), vec!["key1", "key2"]);
+    assert_eq!(keys(keydir.scan(b"key3", 10)), vec!["key3", "key4"]);
+    assert!(keydir.scan(b"key5", 10).is_empty());
+
+    keydir.bump_version(5);
+    keydir.bump_version(3);
+
+    assert_eq!(keydir.get_version(), 5);
+}
+
+#[test]
+fn test_keydir_expiring() {
+    let mut keydir: KeyDir = KeyDir::new();
+
+    for (key, expire) in [("key1", 0), ("key2", 1652000060), ("key3", 1652000060)] {
+        keydir.put(key, KeyDirEntry::new(1, 6, 32, 1652000000, expire, 1));
+    }
+
+    assert_eq!(keydir.expiring_len(), 2);
+    assert_eq!(keydir.sample_expiring(1).len(), 1);
+
+    keydir.remove("key2");
+    keydir.put("key3", KeyDirEntry::new(1, 6, 70, 1652000000, 0, 2));
+
+    assert_eq!(keydir.expiring_len(), 0);
+    assert!(keydir.sample_expiring(10).is_empty());
+
+    keydir.put(
+        "key1",
+        KeyDirEntry::new(1, 6, 32, 1652000000, 1652000060, 3),
+    );
+    keydir.put(
+        "key4",
+        KeyDirEntry::new(1, 6, 32, 1652000000, 1652000060, 4),
+    );
+    keydir.put(
+        "key1",
+        KeyDirEntry::new(1, 6, 32, 1652000000, 1652000090, 5),
+    );
+    keydir.remove("key1");
+
+    let sample = keydir.sample_expiring(10);
+
+    assert_eq!(sample.len(), 1);
+    assert_eq!(sample[0].0, &b"key4".to_vec());
+}
+
+#[test]
+fn test_keydir_range() {
+    for index in [IndexType::Hash, IndexType::BTree] {
+        let mut keydir: KeyDir = KeyDir::with_index(index);
+
+        assert_eq!(keydir.get_index_type(), index);
+
+        for key in ["events:3", "events:1", "users:1", "events:2"] {
+            keydir.put(key, KeyDirEntry::new(1, 6, 32, 1652000000, 0, 1));
+        }
+
+        let keys = |range: Box<dyn Iterator<Item = (&Vec<u8>, &KeyDirEntry)> + '_>| {
+            range
+                .map(|(key, _)| String::from_utf8_lossy(key).to_string())
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(
+            keys(keydir.range(b"events:", Some(b"events:~"), false)),
+            vec!["events:1", "events:2", "events:3"]
+        );
+        assert_eq!(
+            keys(keydir.range(b"events:2", Some(b"users:1"), true)),
+            vec!["users:1", "events:3", "events:2"]
+        );
+        assert_eq!(keys(keydir.range(b"users:", None, false)), vec!["users:1"]);
+        assert!(keys(keydir.range(b"users:", Some(b"events:"), false)).is_empty());
+        assert_eq!(keydir.len(), 4);
+    }
+
+    assert_eq!("BTree".parse::<IndexType>(), Ok(IndexType::BTree));
+    assert_eq!("hash".parse::<IndexType>(), Ok(IndexType::Hash));
+    assert!("tree".parse::<IndexType>().is_err());
+}
+
+#[test]
+fn test_keydir_scan() {
+    for index in [IndexType::Hash, IndexType::BTree] {
+        let mut keydir: KeyDir = KeyDir::with_index(index);
+        let entry = || KeyDirEntry::new(1, 6, 32, 1652000000, 0, 1);
+        let keys = |page: Vec<(&Vec<u8>, &KeyDirEntry)>| {
+            page.into_iter()
+                .map(|(key, _)| String::from_utf8_lossy(key).to_string())
+                .collect::<Vec<String>>()
+        };
+
+        for key in ["key3", "key1", "key2", "key5"] {
+            keydir.put(key, entry());
+        }
+
+        assert_eq!(keys(keydir.scan(b"", 2)), vec!["key1", "key2"]);
+        assert_eq!(keys(keydir.scan(b"", 0)), Vec::<String>::new());
+
+        // The next page sees the keys written since the previous one
+        keydir.remove("key3");
+        keydir.put("key4", entry());
+        keydir.put("key0", entry());
+
+        assert_eq!(keys(keydir.scan(b"key3", 2)), vec!["key4", "key5"]);
+        assert_eq!(
+            keys(keydir.scan(b"", 10)),
+            vec!["key0", "key1", "key2", "key4", "key5"]
+        );
+    }
+}
@@ -2,6 +2,21 @@
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
 
+pub mod bootup;
+pub mod codec;
 pub mod command;
+pub mod compact;
+pub mod connection;
 pub mod database;
+pub mod entry;
+pub mod error;
+pub mod handler;
+pub mod hint;
+pub mod keydir;
+pub mod namespace;
+pub mod reader;
+pub mod resp;
+pub mod response;
+pub mod snapshot;
+pub mod transaction;
 pub mod writer;
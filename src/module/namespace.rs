@@ -0,0 +1,318 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::database::{Database, DEFAULT_COMPACT_RATIO, DEFAULT_MAX_FILE_SIZE};
+use crate::module::error::Error;
+use crate::module::keydir::IndexType;
+use crate::util::lock::{read_lock, write_lock};
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// The namespace connections start in, kept in the storage directory itself
+pub const DEFAULT_NAMESPACE: &str = "0";
+
+/// The directory inside the storage directory holding the other namespaces,
+/// apart from the files the default namespace keeps there
+pub const NAMESPACES_DIR: &str = "namespaces";
+
+/// The longest namespace name
+pub const MAX_NAMESPACE_SIZE: usize = 64;
+
+/// The default number of namespaces that can be loaded, the default one included
+pub const DEFAULT_MAX_NAMESPACES: usize = 16;
+
+/// A database shared by the connections of its namespace
+pub type SharedDatabase = Arc<RwLock<Database>>;
+
+// Namespaces type
+pub struct Namespaces {
+    // The storage directory
+    path: String,
+    // The maximum datafile size of the opened databases
+    max_file_size: u64,
+    // The dead bytes ratio that triggers the compaction of a database
+    compact_ratio: f64,
+    // The keydir index of the opened databases
+    index_type: IndexType,
+    // The number of namespaces that can be loaded
+    max_namespaces: usize,
+    // The opened databases by namespace name
+    databases: RwLock<HashMap<String, SharedDatabase>>,
+}
+
+// Namespaces type methods
+impl Namespaces {
+    ///
+    /// Returns the namespaces stored in a directory
+    ///
+    /// The default namespace uses the storage directory itself and every
+    /// other namespace uses a directory named after it in `namespaces`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the storage directory
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::namespace::Namespaces;
+    ///
+    /// let namespaces: Namespaces = Namespaces::new("/etc/langmore");
+    /// ```
+    ///
+    pub fn new<S: Into<String>>(path: S) -> Namespaces {
+        Namespaces {
+            path: path.into(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            compact_ratio: DEFAULT_COMPACT_RATIO,
+            index_type: IndexType::default(),
+            max_namespaces: DEFAULT_MAX_NAMESPACES,
+            databases: RwLock::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Sets the maximum datafile size of the databases opened afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The maximum datafile size in bytes
+    ///
+    pub fn set_max_file_size(&mut self, size: u64) {
+        self.max_file_size = size;
+    }
+
+    ///
+    /// Sets the compaction ratio of the databases opened afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `ratio` - The dead bytes ratio that triggers a compaction
+    ///
+    pub fn set_compact_ratio(&mut self, ratio: f64) {
+        self.compact_ratio = ratio;
+    }
+
+    ///
+    /// Sets the keydir index of the databases opened afterwards
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The keydir index type
+    ///
+    pub fn set_index_type(&mut self, index: IndexType) {
+        self.index_type = index;
+    }
+
+    ///
+    /// Sets the number of namespaces that can be loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - The maximum number of namespaces, the default one included
+    ///
+    pub fn set_max_namespaces(&mut self, max: usize) {
+        self.max_namespaces = max;
+    }
+
+    ///
+    /// Returns the directory the namespaces are stored under
+    ///
+    /// # Returns
+    ///
+    /// * The path of the default namespace, the others live in its
+    ///   `namespaces` directory
+    ///
+    pub fn get_path(&self) -> String {
+        self.path.to_string()
+    }
+
+    ///
+    /// Checks whether a namespace name is valid
+    ///
+    /// The name is used as a directory, so it is limited to letters, digits,
+    /// `-` and `_`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The namespace name
+    ///
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name.len() <= MAX_NAMESPACE_SIZE
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+
+    ///
+    /// Gets the database of a namespace, loading it on first use
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The namespace name
+    ///
+    /// # Returns
+    ///
+    /// * The shared database of the namespace
+    /// * Error raised if the name is invalid, too many namespaces are loaded
+    ///   or the datafiles can not be loaded
+    ///
+    pub fn open(&self, name: &str) -> Result<SharedDatabase, Error> {
+        if let Some(db) = read_lock(&self.databases).get(name) {
+            return Ok(Arc::clone(db));
+        }
+
+        if !Namespaces::is_valid_name(name) {
+            return Err(Error::Protocol(format!("Invalid namespace `{}`", name)));
+        }
+
+        let mut databases = write_lock(&self.databases);
+
+        // Another connection may have loaded it while waiting for the lock
+        if let Some(db) = databases.get(name) {
+            return Ok(Arc::clone(db));
+        }
+
+        if databases.len() >= self.max_namespaces {
+            return Err(Error::State(format!(
+                "Too many namespaces, the limit is {}",
+                self.max_namespaces
+            )));
+        }
+
+        let mut database = Database::new(self.namespace_path(name));
+
+        database.set_max_file_size(self.max_file_size);
+        database.set_compact_ratio(self.compact_ratio);
+        database.set_index_type(self.index_type);
+        database.load()?;
+
+        let db = Arc::new(RwLock::new(database));
+
+        databases.insert(name.to_string(), Arc::clone(&db));
+
+        Ok(db)
+    }
+
+    ///
+    /// Gets the databases loaded so far
+    ///
+    /// # Returns
+    ///
+    /// * The namespace names and their databases ordered by name
+    ///
+    pub fn loaded(&self) -> Vec<(String, SharedDatabase)> {
+        let mut loaded: Vec<(String, SharedDatabase)> = read_lock(&self.databases)
+            .iter()
+            .map(|(name, db)| (name.to_string(), Arc::clone(db)))
+            .collect();
+
+        loaded.sort_by(|a, b| a.0.cmp(&b.0));
+        loaded
+    }
+
+    // Gets the directory of a namespace
+    fn namespace_path(&self, name: &str) -> String {
+        match name {
+            DEFAULT_NAMESPACE => self.path.to_string(),
+            name => format!("{}/{}/{}", self.path, NAMESPACES_DIR, name),
+        }
+    }
+}
+
+#[test]
+fn test_namespaces_open() {
+    let _ = std::fs::remove_dir_all("./cache/namespaces_open");
+
+    let namespaces: Namespaces = Namespaces::new("./cache/namespaces_open");
+    let default = namespaces.open(DEFAULT_NAMESPACE).unwrap();
+    let orders = namespaces.open("orders").unwrap();
+
+    assert_eq!(
+        default.read().unwrap().get_path(),
+        "./cache/namespaces_open".to_string()
+    );
+    assert_eq!(
+        orders.read().unwrap().get_path(),
+        "./cache/namespaces_open/namespaces/orders".to_string()
+    );
+    assert_eq!(default.write().unwrap().set("key1", "value1"), Ok(1));
+    assert_eq!(orders.write().unwrap().set("key1", "value2"), Ok(1));
+    assert_eq!(orders.write().unwrap().set("key2", "value3"), Ok(2));
+    assert!(Arc::ptr_eq(&orders, &namespaces.open("orders").unwrap()));
+    assert_eq!(namespaces.loaded().len(), 2);
+    assert!(namespaces.open("../orders").is_err());
+    assert!(namespaces.open("").is_err());
+
+    let _ = default.write().unwrap().close();
+    let _ = orders.write().unwrap().close();
+
+    let mut namespaces: Namespaces = Namespaces::new("./cache/namespaces_open");
+
+    namespaces.set_max_namespaces(3);
+    let default = namespaces.open(DEFAULT_NAMESPACE).unwrap();
+    let orders = namespaces.open("orders").unwrap();
+
+    assert_eq!(default.read().unwrap().len(), 1);
+    assert_eq!(
+        default.read().unwrap().get("key1"),
+        Ok(Some(b"value1".to_vec()))
+    );
+    assert_eq!(orders.read().unwrap().len(), 2);
+    assert_eq!(
+        orders.read().unwrap().get("key1"),
+        Ok(Some(b"value2".to_vec()))
+    );
+
+    // A namespace never written leaves no directory behind
+    let empty = namespaces.open("empty").unwrap();
+
+    assert_eq!(empty.write().unwrap().shutdown(), Ok(()));
+    assert!(!std::path::Path::new("./cache/namespaces_open/namespaces/empty").exists());
+    assert_eq!(
+        namespaces.open("users").err(),
+        Some(Error::State(
+            "Too many namespaces, the limit is 3".to_string()
+        ))
+    );
+}
+
+#[test]
+fn test_namespaces_engine_names() {
+    use crate::module::database::CLEAN_SHUTDOWN_FILE;
+
+    let _ = std::fs::remove_dir_all("./cache/namespaces_engine_names");
+
+    let namespaces: Namespaces = Namespaces::new("./cache/namespaces_engine_names");
+    let default = namespaces.open(DEFAULT_NAMESPACE).unwrap();
+
+    // Namespaces named after the files of the default namespace do not
+    // collide with them
+    for name in [CLEAN_SHUTDOWN_FILE, "snapshots", NAMESPACES_DIR] {
+        let db = namespaces.open(name).unwrap();
+
+        assert_eq!(db.write().unwrap().set("key1", name), Ok(1));
+    }
+
+    assert_eq!(default.write().unwrap().set("key1", "value1"), Ok(1));
+
+    for (_, db) in namespaces.loaded() {
+        assert_eq!(db.write().unwrap().shutdown(), Ok(()));
+    }
+
+    let namespaces: Namespaces = Namespaces::new("./cache/namespaces_engine_names");
+    let default = namespaces.open(DEFAULT_NAMESPACE).unwrap();
+    let db = namespaces.open(CLEAN_SHUTDOWN_FILE).unwrap();
+
+    assert_eq!(
+        default.read().unwrap().get("key1"),
+        Ok(Some(b"value1".to_vec()))
+    );
+    assert_eq!(
+        db.read().unwrap().get("key1"),
+        Ok(Some(CLEAN_SHUTDOWN_FILE.as_bytes().to_vec()))
+    );
+}
@@ -0,0 +1,234 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::entry::{
+    read_record, read_record_unverified, LogEntry, Record, HEADER_SIZE,
+};
+use crate::module::error::Error;
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::BufReader;
+
+// Reader type
+pub struct Reader {
+    // The buffered datafile
+    reader: BufReader<File>,
+    // The offset of the next record
+    offset: u64,
+    // The entries of the last batch not yielded yet
+    pending: VecDeque<(u64, LogEntry)>,
+    // Whether the end of the datafile or a bad entry was reached
+    done: bool,
+    // Whether the CRC of each record is checked
+    verify: bool,
+}
+
+// Reader type methods
+impl Reader {
+    ///
+    /// Opens a datafile for iterating over its entries
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the datafile
+    ///
+    /// # Returns
+    ///
+    /// * An instance of the reader object
+    /// * Error raised
+    ///
+    pub fn open(path: String) -> Result<Reader, Error> {
+        match File::open(path.as_str()) {
+            Ok(file) => Ok(Reader {
+                reader: BufReader::new(file),
+                offset: 0,
+                pending: VecDeque::new(),
+                done: false,
+                verify: true,
+            }),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    ///
+    /// Gets the offset of the next record
+    ///
+    /// # Returns
+    ///
+    /// * The offset in bytes from the start of the datafile
+    ///
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    ///
+    /// Sets whether the CRC of each record is checked, on by default
+    ///
+    /// # Arguments
+    ///
+    /// * `verify` - Whether to check the CRC
+    ///
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+}
+
+// Yields each entry along with its offset in the datafile, the entries of
+// a batch are yielded one by one. Iteration stops after the first truncated
+// or corrupted record.
+impl Iterator for Reader {
+    type Item = Result<(u64, LogEntry), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.pop_front() {
+            return Some(Ok(item));
+        }
+
+        if self.done {
+            return None;
+        }
+
+        let record = match self.verify {
+            true => read_record(&mut self.reader),
+            false => read_record_unverified(&mut self.reader),
+        };
+
+        match record {
+            Ok(Some(Record::Entry(entry))) => {
+                let offset = self.offset;
+                self.offset += entry.size();
+                Some(Ok((offset, entry)))
+            }
+            Ok(Some(Record::Batch(entries))) => {
+                let mut offset = self.offset + HEADER_SIZE as u64;
+
+                for entry in entries.into_iter() {
+                    let size = entry.size();
+                    self.pending.push_back((offset, entry));
+                    offset += size;
+                }
+
+                self.offset = offset;
+                self.next()
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(Error::Corruption(format!(
+                    "{} at offset {}",
+                    e.message(),
+                    self.offset
+                ))))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::entry::encode_batch;
+    use crate::module::writer::Writer;
+
+    #[test]
+    /// test iterating over datafile entries
+    fn test_iterate() {
+        let wt: Writer = Writer::new();
+        let path = "cache/reader_iterate.data".to_string();
+        let _ = wt.overwrite(path.to_string(), "".to_string());
+
+        let first = LogEntry::new("key1", "value1");
+        let second = LogEntry::tombstone("key1");
+
+        assert_eq!(wt.append_entry(path.to_string(), &first), Ok(0));
+        assert_eq!(wt.append_entry(path.to_string(), &second), Ok(first.size()));
+
+        let entries: Vec<(u64, LogEntry)> = Reader::open(path)
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect();
+
+        assert_eq!(entries, vec![(0, first.clone()), (first.size(), second)]);
+    }
+
+    #[test]
+    /// test iterating over batch entries
+    fn test_iterate_batch() {
+        let wt: Writer = Writer::new();
+        let path = "cache/reader_batch.data".to_string();
+        let _ = wt.overwrite(path.to_string(), "".to_string());
+
+        let first = LogEntry::new("key1", "value1");
+        let second = LogEntry::new("key2", "value2");
+        let third = LogEntry::new("key3", "value3");
+
+        let _ = wt.append_entry(path.to_string(), &first);
+        let _ = wt.append_bytes(
+            path.to_string(),
+            &encode_batch(&[second.clone(), third.clone()]),
+        );
+        let _ = wt.append_bytes(
+            path.to_string(),
+            &encode_batch(std::slice::from_ref(&first))[0..30],
+        );
+
+        let mut reader = Reader::open(path).unwrap();
+        let batch = first.size() + HEADER_SIZE as u64;
+
+        assert_eq!(reader.next(), Some(Ok((0, first))));
+        assert_eq!(reader.next(), Some(Ok((batch, second.clone()))));
+        assert_eq!(reader.next(), Some(Ok((batch + second.size(), third))));
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    /// test iterating without checking the CRC
+    fn test_unverified() {
+        let wt: Writer = Writer::new();
+        let path = "cache/reader_unverified.data".to_string();
+        let mut buf = LogEntry::new("key1", "value1").encode();
+        let last = buf.len() - 1;
+
+        buf[last] ^= 0xff;
+
+        let _ = wt.overwrite(path.to_string(), "".to_string());
+        let _ = wt.append_bytes(path.to_string(), &buf);
+
+        assert!(Reader::open(path.to_string())
+            .unwrap()
+            .next()
+            .unwrap()
+            .is_err());
+
+        let mut reader = Reader::open(path).unwrap();
+        reader.set_verify(false);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    /// test iteration stops at a truncated entry
+    fn test_truncated() {
+        let wt: Writer = Writer::new();
+        let path = "cache/reader_truncated.data".to_string();
+        let _ = wt.overwrite(path.to_string(), "".to_string());
+
+        let entry = LogEntry::new("key1", "value1");
+        let _ = wt.append_entry(path.to_string(), &entry);
+        let _ = wt.append(path.to_string(), "garbage".to_string());
+
+        let mut reader = Reader::open(path).unwrap();
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.offset(), entry.size());
+    }
+}
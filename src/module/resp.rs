@@ -0,0 +1,657 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::command::{Command, Type};
+use crate::module::error::Error;
+use crate::module::handler::handle;
+use crate::module::namespace::SharedDatabase;
+use crate::module::response::{Code, Response};
+
+use std::collections::BTreeMap;
+
+/// The largest bulk string or array length accepted in a request
+pub const MAX_BULK_LENGTH: usize = 536870912;
+
+/// The number of SCAN cursors a connection keeps, the oldest are dropped first
+pub const MAX_SCAN_CURSORS: usize = 64;
+
+/// A parsed request, its arguments and the number of bytes they used
+pub type Frame = (Vec<Vec<u8>>, usize);
+
+#[derive(Debug, PartialEq, Clone)]
+// Value type
+pub enum Value {
+    // A simple string like `+OK`
+    Simple(String),
+    // An error like `-ERR message`
+    Error(String),
+    // An integer like `:1`
+    Integer(i64),
+    // A binary safe string
+    Bulk(Vec<u8>),
+    // The absence of a value
+    Null,
+    // A list of values
+    Array(Vec<Value>),
+    // Key value pairs, sent as a flat array to RESP2 clients
+    Map(Vec<(Value, Value)>),
+}
+
+// Value type methods
+impl Value {
+    ///
+    /// Encodes the value for the protocol version of the client
+    ///
+    /// # Arguments
+    ///
+    /// * `version` - The RESP version, 2 or 3
+    ///
+    /// # Returns
+    ///
+    /// * The encoded bytes
+    ///
+    pub fn encode(&self, version: u8) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        self.write(version, &mut buf);
+        buf
+    }
+
+    // Appends the encoded value to the buffer
+    fn write(&self, version: u8, buf: &mut Vec<u8>) {
+        match self {
+            Value::Simple(text) => {
+                buf.extend_from_slice(format!("+{}\r\n", text).as_bytes())
+            }
+            Value::Error(text) => {
+                buf.extend_from_slice(format!("-{}\r\n", text).as_bytes())
+            }
+            Value::Integer(n) => buf.extend_from_slice(format!(":{}\r\n", n).as_bytes()),
+            Value::Bulk(data) => {
+                buf.extend_from_slice(format!("${}\r\n", data.len()).as_bytes());
+                buf.extend_from_slice(data);
+                buf.extend_from_slice(b"\r\n");
+            }
+            Value::Null if version >= 3 => buf.extend_from_slice(b"_\r\n"),
+            Value::Null => buf.extend_from_slice(b"$-1\r\n"),
+            Value::Array(items) => {
+                buf.extend_from_slice(format!("*{}\r\n", items.len()).as_bytes());
+
+                for item in items.iter() {
+                    item.write(version, buf);
+                }
+            }
+            Value::Map(pairs) => {
+                match version {
+                    3 => {
+                        buf.extend_from_slice(format!("%{}\r\n", pairs.len()).as_bytes())
+                    }
+                    _ => buf.extend_from_slice(
+                        format!("*{}\r\n", pairs.len() * 2).as_bytes(),
+                    ),
+                }
+
+                for (key, value) in pairs.iter() {
+                    key.write(version, buf);
+                    value.write(version, buf);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Parses a request frame, an array of bulk strings
+///
+/// # Arguments
+///
+/// * `buf` - The received bytes starting with `*`
+///
+/// # Returns
+///
+/// * The arguments and the number of bytes they used, None if the frame is incomplete
+/// * Error raised if the frame is malformed
+///
+pub fn parse(buf: &[u8]) -> Result<Option<Frame>, Error> {
+    let (count, mut pos) = match read_length(buf, 0, b'*')? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    let mut args: Vec<Vec<u8>> = Vec::new();
+
+    for _ in 0..count {
+        let (size, start) = match read_length(buf, pos, b'$')? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        if buf.len() < start + size + 2 {
+            return Ok(None);
+        }
+
+        if &buf[start + size..start + size + 2] != b"\r\n" {
+            return Err(Error::Protocol(
+                "Protocol error: invalid bulk length".to_string(),
+            ));
+        }
+
+        args.push(buf[start..start + size].to_vec());
+        pos = start + size + 2;
+    }
+
+    Ok(Some((args, pos)))
+}
+
+// Reads a `<prefix><length>\r\n` header and returns the length and the
+// position following it
+fn read_length(
+    buf: &[u8],
+    pos: usize,
+    prefix: u8,
+) -> Result<Option<(usize, usize)>, Error> {
+    let line = match buf.get(pos..) {
+        Some(rest) => match rest.windows(2).position(|w| w == b"\r\n") {
+            Some(end) => &rest[..end],
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    if line.first() != Some(&prefix) {
+        return Err(Error::Protocol(format!(
+            "Protocol error: expected '{}', got '{}'",
+            prefix as char,
+            line.first().map(|c| *c as char).unwrap_or(' ')
+        )));
+    }
+
+    match std::str::from_utf8(&line[1..])
+        .ok()
+        .and_then(|n| n.parse::<usize>().ok())
+    {
+        Some(length) if length <= MAX_BULK_LENGTH => {
+            Ok(Some((length, pos + line.len() + 2)))
+        }
+        _ => Err(Error::Protocol(
+            "Protocol error: invalid length".to_string(),
+        )),
+    }
+}
+
+#[derive(Debug)]
+// Resp type
+pub struct Resp {
+    // The protocol version negotiated with HELLO
+    version: u8,
+    // The SCAN cursors handed out and the Langmore cursor each stands for
+    cursors: BTreeMap<u64, Vec<u8>>,
+    // The last SCAN cursor handed out
+    last_cursor: u64,
+}
+
+impl Default for Resp {
+    fn default() -> Self {
+        Resp::new()
+    }
+}
+
+// Resp type methods
+impl Resp {
+    ///
+    /// Returns the state of a RESP connection, RESP2 until HELLO says otherwise
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::resp::Resp;
+    ///
+    /// let resp: Resp = Resp::new();
+    /// ```
+    ///
+    pub fn new() -> Resp {
+        Resp {
+            version: 2,
+            cursors: BTreeMap::new(),
+            last_cursor: 0,
+        }
+    }
+
+    ///
+    /// Gets the negotiated protocol version
+    ///
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    ///
+    /// Executes a request and returns the encoded reply
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    /// * `args` - The command name followed by its arguments
+    ///
+    /// # Returns
+    ///
+    /// * The reply bytes to write back to the client
+    ///
+    pub fn handle(&mut self, db: &SharedDatabase, args: Vec<Vec<u8>>) -> Vec<u8> {
+        let reply = self.execute(db, args);
+
+        reply.encode(self.version)
+    }
+
+    // Executes a request
+    fn execute(&mut self, db: &SharedDatabase, mut args: Vec<Vec<u8>>) -> Value {
+        let name = match args.first() {
+            Some(name) => String::from_utf8_lossy(name).to_uppercase(),
+            None => return Value::Error("ERR empty command".to_string()),
+        };
+
+        // The minimum and maximum number of items including the name
+        let (min, max) = match name.as_str() {
+            "PING" => (1, 2),
+            "GET" | "TTL" | "INCR" | "DECR" | "STRLEN" => (2, 2),
+            "SET" => (3, 5),
+            "DEL" | "EXISTS" | "MGET" => (2, usize::MAX),
+            "MSET" => (3, usize::MAX),
+            "EXPIRE" | "SETNX" | "INCRBY" | "DECRBY" | "APPEND" => (3, 3),
+            "GETRANGE" => (4, 4),
+            "SCAN" => (2, 6),
+            "HELLO" | "COMMAND" => (1, usize::MAX),
+            "QUIT" => (1, 1),
+            _ => {
+                return Value::Error(format!(
+                    "ERR unknown command '{}'",
+                    String::from_utf8_lossy(&args[0])
+                ))
+            }
+        };
+
+        // `MSET` takes whole key value pairs
+        let unpaired = name == "MSET" && args.len().is_multiple_of(2);
+
+        if args.len() < min || args.len() > max || unpaired {
+            return Value::Error(format!(
+                "ERR wrong number of arguments for '{}' command",
+                name.to_lowercase()
+            ));
+        }
+
+        match name.as_str() {
+            "HELLO" => return self.hello(&args),
+            "COMMAND" => return Value::Array(Vec::new()),
+            "PING" if args.len() == 2 => return Value::Bulk(args[1].to_vec()),
+            "EXISTS" => return exists(db, &args[1..]),
+            _ => {}
+        }
+
+        // Redis clients expect a numeric cursor, it is swapped for the key
+        // the scan resumes at
+        if name == "SCAN" {
+            match self.resume(&args[1]) {
+                Some(cursor) => args[1] = cursor,
+                None => return Value::Error("ERR invalid cursor".to_string()),
+            }
+        }
+
+        let cmd = match command(name.as_str(), args) {
+            Ok(cmd) => cmd,
+            Err(e) => return Value::Error(e),
+        };
+
+        match (cmd.get_name(), handle(db, &cmd)) {
+            (Type::Scan, Response::Array(mut page)) if page.len() == 2 => {
+                let keys = Value::from(page.pop().unwrap_or(Response::Nil));
+                let next = match page.pop() {
+                    Some(Response::Value(cursor)) => self.save(cursor),
+                    _ => 0,
+                };
+
+                Value::Array(vec![Value::Bulk(next.to_string().into_bytes()), keys])
+            }
+            (_, response) => reply(&cmd, response),
+        }
+    }
+
+    // Gets the Langmore cursor a SCAN cursor stands for, `0` for the first page
+    fn resume(&self, cursor: &[u8]) -> Option<Vec<u8>> {
+        match String::from_utf8_lossy(cursor).parse::<u64>().ok()? {
+            0 => Some(b"0".to_vec()),
+            id => self.cursors.get(&id).cloned(),
+        }
+    }
+
+    // Keeps a Langmore cursor and returns the SCAN cursor standing for it
+    fn save(&mut self, cursor: Vec<u8>) -> u64 {
+        if cursor == b"0" {
+            return 0;
+        }
+
+        self.last_cursor += 1;
+        self.cursors.insert(self.last_cursor, cursor);
+
+        if self.cursors.len() > MAX_SCAN_CURSORS {
+            self.cursors.pop_first();
+        }
+
+        self.last_cursor
+    }
+
+    // Switches the protocol version and describes the server
+    fn hello(&mut self, args: &[Vec<u8>]) -> Value {
+        if let Some(version) = args.get(1) {
+            match String::from_utf8_lossy(version).as_ref() {
+                "2" => self.version = 2,
+                "3" => self.version = 3,
+                _ => {
+                    return Value::Error(
+                        "NOPROTO unsupported protocol version".to_string(),
+                    )
+                }
+            }
+        }
+
+        let text = |text: &str| Value::Bulk(text.as_bytes().to_vec());
+
+        Value::Map(vec![
+            (text("server"), text("langmore")),
+            (text("version"), text(env!("CARGO_PKG_VERSION"))),
+            (text("proto"), Value::Integer(self.version as i64)),
+            (text("mode"), text("standalone")),
+            (text("role"), text("master")),
+            (text("modules"), Value::Array(Vec::new())),
+        ])
+    }
+}
+
+// Maps a Redis request onto a Langmore command
+fn command(name: &str, mut args: Vec<Vec<u8>>) -> Result<Command, String> {
+    args[0] = match name {
+        "QUIT" => b"EXIT".to_vec(),
+        "DEL" => b"MDELETE".to_vec(),
+        name => name.as_bytes().to_vec(),
+    };
+
+    // `SET key value EX secs` or `PX millis` carries the expiry
+    if name == "SET" && args.len() > 3 {
+        let unit = String::from_utf8_lossy(&args[3]).to_uppercase();
+        let amount = args
+            .get(4)
+            .and_then(|amount| String::from_utf8_lossy(amount).parse::<u64>().ok());
+
+        let secs = match (unit.as_str(), amount) {
+            ("EX", Some(secs)) if secs > 0 => secs,
+            ("PX", Some(millis)) if millis > 0 => millis.div_ceil(1000),
+            ("EX", _) | ("PX", _) if args.len() == 5 => {
+                return Err("ERR invalid expire time in 'set' command".to_string())
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        };
+
+        args.truncate(3);
+        args.push(secs.to_string().into_bytes());
+    }
+
+    Command::from_args(args.clone()).map_err(|_| refused(name, &args))
+}
+
+// Gets the Redis error of a request the command parser refused
+fn refused(name: &str, args: &[Vec<u8>]) -> String {
+    let integer = |arg: &Vec<u8>| String::from_utf8_lossy(arg).parse::<i64>().is_ok();
+
+    if args.len() < 2 {
+        return format!(
+            "ERR wrong number of arguments for '{}' command",
+            name.to_lowercase()
+        );
+    }
+
+    if name != "SCAN" {
+        return "ERR value is not an integer or out of range".to_string();
+    }
+
+    // Only a `COUNT` that is not a number is reported as such
+    let counted = args[2..].chunks(2).all(|option| {
+        !String::from_utf8_lossy(&option[0]).eq_ignore_ascii_case("COUNT")
+            || option.get(1).is_none_or(integer)
+    });
+
+    match counted {
+        true => "ERR syntax error".to_string(),
+        false => "ERR value is not an integer or out of range".to_string(),
+    }
+}
+
+// Converts a Langmore reply into the reply Redis sends for the command
+fn reply(cmd: &Command, response: Response) -> Value {
+    match (cmd.get_name(), response) {
+        (Type::Expire, Response::Error(Code::NotFound, _)) => Value::Integer(0),
+        (Type::Expire, Response::Status(_)) => Value::Integer(1),
+        (Type::Ttl, Response::Nil) => Value::Integer(-2),
+        (Type::MSet, Response::Array(_)) => Value::Simple("OK".to_string()),
+        (Type::SetNx, Response::Integer(version)) => {
+            Value::Integer((version > 0) as i64)
+        }
+        (Type::MDelete, Response::Array(items)) => {
+            Value::Integer(items.iter().filter(|item| !item.is_error()).count() as i64)
+        }
+        (_, response) => Value::from(response),
+    }
+}
+
+// Counts the existing keys of EXISTS, a repeated key is counted each time
+fn exists(db: &SharedDatabase, keys: &[Vec<u8>]) -> Value {
+    let mut count = 0;
+
+    for key in keys.iter() {
+        match handle(db, &Command::new(key.to_vec(), Vec::new(), 0, Type::Exists)) {
+            Response::Integer(found) => count += found,
+            response => return Value::from(response),
+        }
+    }
+
+    Value::Integer(count)
+}
+
+// The RESP value of a Langmore reply
+impl From<Response> for Value {
+    fn from(response: Response) -> Self {
+        match response {
+            Response::Status(text) => Value::Simple(text),
+            Response::Value(value) => Value::Bulk(value),
+            Response::Nil => Value::Null,
+            Response::Integer(n) => Value::Integer(n),
+            Response::Error(Code::Value, _) => {
+                Value::Error("ERR value is not an integer or out of range".to_string())
+            }
+            Response::Error(_, message) => Value::Error(format!("ERR {}", message)),
+            Response::Array(items) => {
+                Value::Array(items.into_iter().map(Value::from).collect())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::database::Database;
+    use std::sync::{Arc, RwLock};
+
+    // Encodes a request the way Redis clients do
+    fn request(args: &[&str]) -> Vec<u8> {
+        Value::Array(
+            args.iter()
+                .map(|arg| Value::Bulk(arg.as_bytes().to_vec()))
+                .collect(),
+        )
+        .encode(2)
+    }
+
+    #[test]
+    /// test parse method
+    fn test_parse() {
+        let buf = request(&["SET", "key", "a b\r\n"]);
+
+        assert_eq!(
+            parse(&buf),
+            Ok(Some((
+                vec![b"SET".to_vec(), b"key".to_vec(), b"a b\r\n".to_vec()],
+                buf.len()
+            )))
+        );
+        assert_eq!(parse(&buf[0..buf.len() - 1]), Ok(None));
+        assert_eq!(parse(b"*1\r\n$3\r\nGE"), Ok(None));
+        assert!(parse(b"*1\r\n:3\r\n").is_err());
+        assert!(parse(b"*1\r\n$2\r\nabc\r\n").is_err());
+        assert!(parse(b"*x\r\n").is_err());
+    }
+
+    #[test]
+    /// test encode method
+    fn test_encode() {
+        let map = Value::Map(vec![(Value::Simple("a".to_string()), Value::Integer(1))]);
+
+        assert_eq!(
+            Value::Simple("OK".to_string()).encode(2),
+            b"+OK\r\n".to_vec()
+        );
+        assert_eq!(
+            Value::Error("ERR x".to_string()).encode(2),
+            b"-ERR x\r\n".to_vec()
+        );
+        assert_eq!(Value::Integer(-2).encode(2), b":-2\r\n".to_vec());
+        assert_eq!(
+            Value::Bulk(b"ab".to_vec()).encode(2),
+            b"$2\r\nab\r\n".to_vec()
+        );
+        assert_eq!(Value::Null.encode(2), b"$-1\r\n".to_vec());
+        assert_eq!(Value::Null.encode(3), b"_\r\n".to_vec());
+        assert_eq!(map.encode(2), b"*2\r\n+a\r\n:1\r\n".to_vec());
+        assert_eq!(map.encode(3), b"%1\r\n+a\r\n:1\r\n".to_vec());
+    }
+
+    #[test]
+    /// test handle method
+    fn test_handle() {
+        let _ = std::fs::remove_dir_all("./cache/resp_handle");
+
+        let db = Arc::new(RwLock::new(Database::new("./cache/resp_handle")));
+        let mut resp: Resp = Resp::new();
+        let mut run = |args: &[&str]| {
+            let (args, _) = parse(&request(args)).unwrap().unwrap();
+            resp.handle(&db, args)
+        };
+
+        assert_eq!(run(&["PING"]), b"+PONG\r\n".to_vec());
+        assert_eq!(run(&["ping", "hi"]), b"$2\r\nhi\r\n".to_vec());
+        assert_eq!(run(&["GET", "key1"]), b"$-1\r\n".to_vec());
+        assert_eq!(run(&["SET", "key1", "a b"]), b"+OK\r\n".to_vec());
+        assert_eq!(run(&["GET", "key1"]), b"$3\r\na b\r\n".to_vec());
+        assert_eq!(run(&["TTL", "key1"]), b":-1\r\n".to_vec());
+        assert_eq!(run(&["TTL", "key2"]), b":-2\r\n".to_vec());
+        assert_eq!(run(&["SET", "key2", "v", "EX", "100"]), b"+OK\r\n".to_vec());
+        assert_eq!(
+            run(&["SET", "key3", "v", "PX", "1500"]),
+            b"+OK\r\n".to_vec()
+        );
+        assert_eq!(run(&["TTL", "key3"]), b":2\r\n".to_vec());
+        assert_eq!(run(&["EXPIRE", "key1", "100"]), b":1\r\n".to_vec());
+        assert_eq!(run(&["EXPIRE", "key9", "100"]), b":0\r\n".to_vec());
+        assert_eq!(run(&["EXISTS", "key1", "key2", "key9"]), b":2\r\n".to_vec());
+        assert_eq!(run(&["DEL", "key1", "key9"]), b":1\r\n".to_vec());
+        assert_eq!(
+            run(&["MSET", "key4", "a", "key5", "b"]),
+            b"+OK\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["MGET", "key4", "key9", "key5"]),
+            b"*3\r\n$1\r\na\r\n$-1\r\n$1\r\nb\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["MSET", "key4", "a", "key5"]),
+            b"-ERR wrong number of arguments for 'mset' command\r\n".to_vec()
+        );
+        assert_eq!(run(&["EXISTS", "key1"]), b":0\r\n".to_vec());
+        assert_eq!(run(&["SETNX", "key1", "a"]), b":1\r\n".to_vec());
+        assert_eq!(run(&["SETNX", "key1", "b"]), b":0\r\n".to_vec());
+        assert_eq!(run(&["DEL", "key1"]), b":1\r\n".to_vec());
+        assert_eq!(run(&["INCR", "hits"]), b":1\r\n".to_vec());
+        assert_eq!(run(&["INCRBY", "hits", "9"]), b":10\r\n".to_vec());
+        assert_eq!(run(&["DECRBY", "hits", "3"]), b":7\r\n".to_vec());
+        assert_eq!(run(&["DECR", "hits"]), b":6\r\n".to_vec());
+        assert_eq!(
+            run(&["INCR", "key2"]),
+            b"-ERR value is not an integer or out of range\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["INCRBY", "hits", "x"]),
+            b"-ERR value is not an integer or out of range\r\n".to_vec()
+        );
+        assert_eq!(run(&["DEL", "hits"]), b":1\r\n".to_vec());
+        assert_eq!(run(&["APPEND", "log", "ab"]), b":2\r\n".to_vec());
+        assert_eq!(run(&["APPEND", "log", "cd"]), b":4\r\n".to_vec());
+        assert_eq!(run(&["STRLEN", "log"]), b":4\r\n".to_vec());
+        assert_eq!(
+            run(&["GETRANGE", "log", "1", "-2"]),
+            b"$2\r\nbc\r\n".to_vec()
+        );
+        assert_eq!(run(&["DEL", "log"]), b":1\r\n".to_vec());
+        assert_eq!(
+            run(&["SCAN", "0", "MATCH", "key*", "COUNT", "100"]),
+            b"*2\r\n$1\r\n0\r\n*4\r\n\
+            $4\r\nkey2\r\n$4\r\nkey3\r\n$4\r\nkey4\r\n$4\r\nkey5\r\n"
+                .to_vec()
+        );
+        assert_eq!(
+            run(&["SET", "key1", "v", "EX", "0"]),
+            b"-ERR invalid expire time in 'set' command\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["SET", "key1", "v", "NX"]),
+            b"-ERR syntax error\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["SCAN", "0", "LIMIT", "10"]),
+            b"-ERR syntax error\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["SCAN", "0", "COUNT", "x"]),
+            b"-ERR value is not an integer or out of range\r\n".to_vec()
+        );
+        assert_eq!(run(&["SCAN", "x"]), b"-ERR invalid cursor\r\n".to_vec());
+        assert_eq!(
+            run(&["EXPIRE", "key2", "soon"]),
+            b"-ERR value is not an integer or out of range\r\n".to_vec()
+        );
+        assert_eq!(run(&["SCAN", "7"]), b"-ERR invalid cursor\r\n".to_vec());
+        assert_eq!(
+            run(&["SCAN", "0", "MATCH", "key*", "COUNT", "2"]),
+            b"*2\r\n$1\r\n1\r\n*2\r\n$4\r\nkey2\r\n$4\r\nkey3\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["SCAN", "1", "MATCH", "key*", "COUNT", "2"]),
+            b"*2\r\n$1\r\n0\r\n*2\r\n$4\r\nkey4\r\n$4\r\nkey5\r\n".to_vec()
+        );
+        assert_eq!(run(&["EXISTS", "key2", "key2"]), b":2\r\n".to_vec());
+        assert_eq!(run(&["DEL", "key2", "key2"]), b":1\r\n".to_vec());
+        assert_eq!(
+            run(&["GET"]),
+            b"-ERR wrong number of arguments for 'get' command\r\n".to_vec()
+        );
+        assert_eq!(
+            run(&["FLUSHALL"]),
+            b"-ERR unknown command 'FLUSHALL'\r\n".to_vec()
+        );
+        assert_eq!(run(&["COMMAND", "DOCS"]), b"*0\r\n".to_vec());
+        assert_eq!(
+            run(&["HELLO", "4"]),
+            b"-NOPROTO unsupported protocol version\r\n".to_vec()
+        );
+        assert!(run(&["HELLO", "3"]).starts_with(b"%6\r\n"));
+        assert_eq!(run(&["GET", "key9"]), b"_\r\n".to_vec());
+    }
+}
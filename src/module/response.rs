@@ -0,0 +1,194 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use crate::module::command::quoted;
+use crate::module::error::Error;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+// Code type
+pub enum Code {
+    // The command can not be parsed
+    Syntax,
+    // The key or the snapshot does not exist
+    NotFound,
+    // The storage failed
+    Io,
+    // The command is not allowed in the current state
+    State,
+    // The command exceeds the maximum size
+    TooLarge,
+    // The stored value can not be used by the command
+    Value,
+}
+
+// Code type methods
+impl Code {
+    ///
+    /// Gets the code written on the wire
+    ///
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::Syntax => "ERR_SYNTAX",
+            Code::NotFound => "ERR_NOT_FOUND",
+            Code::Io => "ERR_IO",
+            Code::State => "ERR_STATE",
+            Code::TooLarge => "ERR_TOO_LARGE",
+            Code::Value => "ERR_VALUE",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+// Response type
+pub enum Response {
+    // A status word like `OK` or `PONG`
+    Status(String),
+    // A stored value, always written quoted
+    Value(Vec<u8>),
+    // The key does not exist
+    Nil,
+    // A number like a time to live
+    Integer(i64),
+    // A failure with its code and message
+    Error(Code, String),
+    // A list of responses
+    Array(Vec<Response>),
+}
+
+// Response type methods
+impl Response {
+    ///
+    /// Returns the `OK` status
+    ///
+    pub fn ok() -> Response {
+        Response::Status("OK".to_string())
+    }
+
+    ///
+    /// Returns an error response
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The error code
+    /// * `message` - The error message
+    ///
+    pub fn error<S: Into<String>>(code: Code, message: S) -> Response {
+        Response::Error(code, message.into())
+    }
+
+    ///
+    /// Returns the error replied when a command needs an existing key
+    ///
+    pub fn not_found() -> Response {
+        Response::error(Code::NotFound, "Key not found")
+    }
+
+    ///
+    /// Whether the response is an error
+    ///
+    pub fn is_error(&self) -> bool {
+        matches!(self, Response::Error(_, _))
+    }
+
+    ///
+    /// Encodes the response for the plain text protocol
+    ///
+    /// Every response starts on its own line and the first character tells
+    /// them apart, a value is quoted, `NIL` is a missing key, a number is an
+    /// integer, `ERR_` starts an error and `*N` announces the N responses
+    /// of an array. Anything else is a status.
+    ///
+    /// # Returns
+    ///
+    /// * The encoded bytes
+    ///
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        self.write(&mut buf);
+        buf
+    }
+
+    // Appends the encoded response to the buffer
+    fn write(&self, buf: &mut Vec<u8>) {
+        let line = match self {
+            Response::Status(text) => text.to_string(),
+            Response::Value(value) => quoted(value),
+            Response::Nil => "NIL".to_string(),
+            Response::Integer(n) => n.to_string(),
+            Response::Error(code, message) => {
+                format!("{} {}", code.as_str(), message.replace('\n', " "))
+            }
+            Response::Array(items) => {
+                buf.extend_from_slice(format!("*{}\n", items.len()).as_bytes());
+
+                for item in items.iter() {
+                    item.write(buf);
+                }
+
+                return;
+            }
+        };
+
+        buf.extend_from_slice(line.as_bytes());
+        buf.push(b'\n');
+    }
+}
+
+// The code of an error raised by the engine or the protocol
+impl From<Error> for Response {
+    fn from(e: Error) -> Self {
+        let code = match e {
+            Error::Protocol(_) => Code::Syntax,
+            Error::NotFound(_) => Code::NotFound,
+            Error::State(_) => Code::State,
+            Error::Io(_) | Error::Corruption(_) | Error::Config(_) => Code::Io,
+        };
+
+        Response::Error(code, e.to_string())
+    }
+}
+
+#[test]
+fn test_response_encode() {
+    assert_eq!(Response::ok().encode(), b"OK\n".to_vec());
+    assert_eq!(
+        Response::Value(b"OK".to_vec()).encode(),
+        b"\"OK\"\n".to_vec()
+    );
+    assert_eq!(Response::Value(Vec::new()).encode(), b"\"\"\n".to_vec());
+    assert_eq!(
+        Response::Value(b"a \"b\"\n".to_vec()).encode(),
+        b"\"a \\\"b\\\"\\n\"\n".to_vec()
+    );
+    assert_eq!(Response::Nil.encode(), b"NIL\n".to_vec());
+    assert_eq!(Response::Integer(-1).encode(), b"-1\n".to_vec());
+    assert_eq!(
+        Response::not_found().encode(),
+        b"ERR_NOT_FOUND Key not found\n".to_vec()
+    );
+    assert_eq!(
+        Response::Array(vec![
+            Response::ok(),
+            Response::Array(vec![Response::Value(b"a".to_vec())]),
+            Response::Nil,
+        ])
+        .encode(),
+        b"*3\nOK\n*1\n\"a\"\nNIL\n".to_vec()
+    );
+    assert!(Response::error(Code::Io, "Disk full").is_error());
+    assert!(!Response::Nil.is_error());
+    assert_eq!(
+        Response::from(Error::Io("disk full".to_string())).encode(),
+        b"ERR_IO Error raised: disk full\n".to_vec()
+    );
+    assert_eq!(
+        Response::from(Error::NotFound("Snapshot `x` not found".to_string())),
+        Response::error(Code::NotFound, "Snapshot `x` not found")
+    );
+    assert_eq!(
+        Response::from(Error::State("Snapshot `x` already exists".to_string())),
+        Response::error(Code::State, "Snapshot `x` already exists")
+    );
+}
@@ -1,3 +1,388 @@
 // Copyright 2022 Clivern. All rights reserved.
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
+
+use crate::module::bootup::Bootup;
+use crate::module::database::Database;
+use crate::module::error::Error;
+use crate::util::lock::{read_lock, write_lock};
+
+use std::fs::{
+    copy, create_dir, create_dir_all, hard_link, read_dir, remove_dir_all, rename, File,
+};
+use std::io::{self, ErrorKind, Read};
+use std::path::Path;
+use std::sync::RwLock;
+
+/// The directory the snapshot files are linked into before a restore
+const RESTORE_STAGING_DIR: &str = "restore.tmp";
+
+/// The directory the live files are moved into during a restore
+const RESTORE_ASIDE_DIR: &str = "restore.old";
+
+// Snapshot type
+pub struct Snapshot {
+    // The directory holding the snapshots
+    path: String,
+}
+
+// Snapshot type methods
+impl Snapshot {
+    ///
+    /// Returns a snapshot object for the snapshots directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the snapshots directory
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::snapshot::Snapshot;
+    ///
+    /// let snapshot: Snapshot = Snapshot::new("/etc/langmore/snapshots");
+    /// ```
+    ///
+    pub fn new<S: Into<String>>(path: S) -> Snapshot {
+        Snapshot { path: path.into() }
+    }
+
+    ///
+    /// Checks whether a snapshot name is valid
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The snapshot name
+    ///
+    pub fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with('.')
+            && !name.ends_with(".tmp")
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    }
+
+    ///
+    /// Copies a consistent state of the database into a named snapshot
+    ///
+    /// The snapshot is built in a temporary directory which is created
+    /// exclusively, so two requests for the same name never share it. The
+    /// sealed datafiles are hard linked and the active datafile is cut at
+    /// its current size while holding the read lock. The cut is copied
+    /// after the lock is released since bytes before it never change.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The snapshot name
+    /// * `db` - The shared database
+    ///
+    /// # Returns
+    ///
+    /// * Error raised
+    ///
+    pub fn create(&self, name: &str, db: &RwLock<Database>) -> Result<(), Error> {
+        if !Snapshot::is_valid_name(name) {
+            return Err(Error::Protocol(format!("Invalid snapshot name `{}`", name)));
+        }
+
+        let target = self.snapshot_path(name);
+        let tmp = format!("{}.tmp", target);
+
+        create_dir_all(self.path.as_str())?;
+
+        if let Err(e) = create_dir(tmp.as_str()) {
+            return match e.kind() {
+                ErrorKind::AlreadyExists => Err(Error::State(format!(
+                    "Snapshot `{}` is being created",
+                    name
+                ))),
+                _ => Err(Error::from(e)),
+            };
+        }
+
+        // Only the owner of the temporary directory renames it to the target
+        let created = if Path::new(target.as_str()).exists() {
+            Err(Error::State(format!("Snapshot `{}` already exists", name)))
+        } else {
+            fill(tmp.as_str(), db)
+                .and_then(|_| rename(tmp.as_str(), target.as_str()).map_err(Error::from))
+        };
+
+        if created.is_err() {
+            let _ = remove_dir_all(tmp.as_str());
+        }
+
+        created
+    }
+
+    ///
+    /// Lists the snapshot names
+    ///
+    /// # Returns
+    ///
+    /// * The snapshot names in ascending order
+    /// * Error raised
+    ///
+    pub fn list(&self) -> Result<Vec<String>, Error> {
+        let mut names: Vec<String> = Vec::new();
+
+        let dir = match read_dir(self.path.as_str()) {
+            Ok(dir) => dir,
+            Err(_) => return Ok(names),
+        };
+
+        for item in dir {
+            let item = item?;
+            let name = item.file_name().to_string_lossy().to_string();
+
+            if item.path().is_dir() && Snapshot::is_valid_name(name.as_str()) {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    ///
+    /// Replaces the database datafiles with the ones of a snapshot
+    ///
+    /// The snapshot files are linked into a staging directory first, so a
+    /// failure leaves the database untouched. The live files are then moved
+    /// aside, the staged ones moved in and the old ones removed last.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The snapshot name
+    /// * `db` - The shared database
+    ///
+    /// # Returns
+    ///
+    /// * The number of records in the restored database
+    /// * Error raised
+    ///
+    pub fn restore(&self, name: &str, db: &RwLock<Database>) -> Result<usize, Error> {
+        let source = self.snapshot_path(name);
+
+        if !Snapshot::is_valid_name(name) || !Path::new(source.as_str()).is_dir() {
+            return Err(Error::NotFound(format!("Snapshot `{}` not found", name)));
+        }
+
+        let mut db = write_lock(db);
+
+        if db.is_compacting() {
+            return Err(Error::State(
+                "A snapshot can not be restored during a compaction".to_string(),
+            ));
+        }
+
+        let staging = format!("{}/{}", db.get_path(), RESTORE_STAGING_DIR);
+        let aside = format!("{}/{}", db.get_path(), RESTORE_ASIDE_DIR);
+
+        let _ = remove_dir_all(staging.as_str());
+        let _ = remove_dir_all(aside.as_str());
+
+        let staged = stage(source.as_str(), staging.as_str());
+
+        if let Err(e) = staged {
+            let _ = remove_dir_all(staging.as_str());
+            return Err(e);
+        }
+
+        db.close()?;
+
+        let live: Vec<String> = files(db.get_path().as_str())?;
+
+        create_dir_all(aside.as_str())?;
+
+        if let Err(e) = move_files(db.get_path().as_str(), aside.as_str(), &live) {
+            let _ = move_files(aside.as_str(), db.get_path().as_str(), &live);
+            db.load()?;
+            return Err(e);
+        }
+
+        let restored: Vec<String> = files(staging.as_str())?;
+
+        if let Err(e) = move_files(staging.as_str(), db.get_path().as_str(), &restored) {
+            let _ = move_files(db.get_path().as_str(), staging.as_str(), &restored);
+            let _ = move_files(aside.as_str(), db.get_path().as_str(), &live);
+            db.load()?;
+            return Err(e);
+        }
+
+        let _ = remove_dir_all(staging.as_str());
+        let _ = remove_dir_all(aside.as_str());
+
+        db.load()
+    }
+
+    // Gets the path of a snapshot
+    fn snapshot_path(&self, name: &str) -> String {
+        format!("{}/{}", self.path, name)
+    }
+}
+
+// Links the sealed datafiles into a directory and copies the active one
+fn fill(tmp: &str, db: &RwLock<Database>) -> Result<(), Error> {
+    let cut = {
+        let db = read_lock(db);
+        let active_id = db.get_active_id();
+
+        for id in db.datafile_ids()? {
+            if id >= active_id {
+                continue;
+            }
+
+            link(db.datafile_path(id), format!("{}/{}.data", tmp, id))?;
+
+            if Path::new(db.hintfile_path(id).as_str()).exists() {
+                link(db.hintfile_path(id), format!("{}/{}.hint", tmp, id))?;
+            }
+        }
+
+        match File::open(db.datafile_path(active_id)) {
+            Ok(file) => Some((active_id, file, db.get_active_size())),
+            Err(_) => None,
+        }
+    };
+
+    if let Some((active_id, file, size)) = cut {
+        let mut out = File::create(format!("{}/{}.data", tmp, active_id))?;
+
+        io::copy(&mut file.take(size), &mut out)?;
+        out.sync_all()?;
+    }
+
+    Ok(())
+}
+
+// Links the datafiles and hint files of a snapshot into a new directory
+fn stage(source: &str, target: &str) -> Result<(), Error> {
+    create_dir_all(target)?;
+
+    for id in Bootup::new(source).datafile_ids()? {
+        link(
+            format!("{}/{}.data", source, id),
+            format!("{}/{}.data", target, id),
+        )?;
+
+        let hint = format!("{}/{}.hint", source, id);
+
+        if Path::new(hint.as_str()).exists() {
+            link(hint, format!("{}/{}.hint", target, id))?;
+        }
+    }
+
+    Ok(())
+}
+
+// Gets the names of the datafiles and hint files of a directory
+fn files(path: &str) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = Vec::new();
+
+    for id in Bootup::new(path).datafile_ids()? {
+        names.push(format!("{}.data", id));
+
+        if Path::new(format!("{}/{}.hint", path, id).as_str()).exists() {
+            names.push(format!("{}.hint", id));
+        }
+    }
+
+    Ok(names)
+}
+
+// Moves files between two directories of the same filesystem
+fn move_files(from: &str, to: &str, names: &[String]) -> Result<(), Error> {
+    for name in names.iter() {
+        rename(format!("{}/{}", from, name), format!("{}/{}", to, name))?;
+    }
+
+    Ok(())
+}
+
+// Hard links a file and falls back to copying it
+fn link(from: String, to: String) -> Result<(), Error> {
+    hard_link(from.as_str(), to.as_str())
+        .or_else(|_| copy(from.as_str(), to.as_str()).map(|_| ()))
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// test create, list and restore methods
+    fn test_create_restore() {
+        let _ = std::fs::remove_dir_all("./cache/snapshot_create");
+
+        let mut database: Database = Database::new("./cache/snapshot_create");
+        database.set_max_file_size(92);
+
+        let db = RwLock::new(database);
+        let snapshot: Snapshot = Snapshot::new("./cache/snapshot_create/snapshots");
+
+        {
+            let mut db = db.write().unwrap();
+            let _ = db.set("key1", "value1");
+            let _ = db.set("key2", "value2");
+            let _ = db.set("key3", "value3");
+        }
+
+        assert_eq!(snapshot.list(), Ok(vec![]));
+        assert_eq!(snapshot.create("backup", &db), Ok(()));
+        assert_eq!(
+            snapshot.create("backup", &db),
+            Err(Error::State("Snapshot `backup` already exists".to_string()))
+        );
+        assert_eq!(
+            snapshot.create("../backup", &db),
+            Err(Error::Protocol(
+                "Invalid snapshot name `../backup`".to_string()
+            ))
+        );
+        assert_eq!(snapshot.list(), Ok(vec!["backup".to_string()]));
+
+        // A snapshot being created holds its temporary directory
+        let _ = create_dir_all("./cache/snapshot_create/snapshots/pending.tmp");
+
+        assert_eq!(
+            snapshot.create("pending", &db),
+            Err(Error::State(
+                "Snapshot `pending` is being created".to_string()
+            ))
+        );
+        assert_eq!(snapshot.list(), Ok(vec!["backup".to_string()]));
+
+        {
+            let mut db = db.write().unwrap();
+            let _ = db.set("key1", "value4");
+            let _ = db.remove("key3");
+            let _ = db.set("key4", "value5");
+            let _ = db.compact();
+        }
+
+        assert_eq!(snapshot.restore("backup", &db), Ok(3));
+        assert_eq!(
+            snapshot.restore("missing", &db),
+            Err(Error::NotFound("Snapshot `missing` not found".to_string()))
+        );
+
+        assert!(!Path::new("./cache/snapshot_create/restore.tmp").exists());
+        assert!(!Path::new("./cache/snapshot_create/restore.old").exists());
+
+        // A snapshot that can not be staged leaves the database untouched
+        let _ = create_dir_all("./cache/snapshot_create/snapshots/broken/1.data");
+
+        assert!(snapshot.restore("broken", &db).is_err());
+
+        let db = db.read().unwrap();
+
+        assert_eq!(db.get("key1"), Ok(Some(b"value1".to_vec())));
+        assert_eq!(db.get("key3"), Ok(Some(b"value3".to_vec())));
+        assert_eq!(db.get("key4"), Ok(None));
+        assert_eq!(db.get_active_id(), 3);
+    }
+}
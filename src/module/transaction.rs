@@ -1,3 +1,250 @@
 // Copyright 2022 Clivern. All rights reserved.
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
+
+use crate::module::command::{Command, Type};
+use crate::module::database::{Database, Operation};
+use crate::module::entry::expire_at;
+use crate::module::response::Response;
+use crate::util::lock::write_lock;
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Debug, Default)]
+// Transaction type
+pub struct Transaction {
+    // The queued write commands
+    commands: Vec<Command>,
+}
+
+// Transaction type methods
+impl Transaction {
+    ///
+    /// Returns an empty transaction
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use langmore::module::transaction::Transaction;
+    ///
+    /// let transaction: Transaction = Transaction::new();
+    /// ```
+    ///
+    pub fn new() -> Transaction {
+        Transaction {
+            commands: Vec::new(),
+        }
+    }
+
+    ///
+    /// Whether the command can be queued in a transaction
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The parsed command
+    ///
+    pub fn accepts(cmd: &Command) -> bool {
+        matches!(
+            *cmd.get_name(),
+            Type::Set | Type::Update | Type::Delete | Type::MSet | Type::MDelete
+        )
+    }
+
+    ///
+    /// Whether the command writes but can not be queued in a transaction
+    ///
+    /// Conditional writes and counters reply from the current value, which
+    /// is not known until `EXEC`. Snapshots and compaction act on the whole
+    /// database and would bypass the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The parsed command
+    ///
+    pub fn rejects(cmd: &Command) -> bool {
+        matches!(
+            *cmd.get_name(),
+            Type::SetNx
+                | Type::Cas
+                | Type::SetV
+                | Type::Incr
+                | Type::Decr
+                | Type::IncrBy
+                | Type::DecrBy
+                | Type::Append
+                | Type::Expire
+                | Type::Persist
+                | Type::Snapshot
+                | Type::Restore
+                | Type::Compact
+        )
+    }
+
+    ///
+    /// Queues a write command
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The parsed command
+    ///
+    pub fn queue(&mut self, cmd: Command) {
+        self.commands.push(cmd);
+    }
+
+    ///
+    /// The number of queued commands
+    ///
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    ///
+    /// Whether no command is queued
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    ///
+    /// Applies the queued commands atomically
+    ///
+    /// The commands are checked in order under the write lock, an `UPDATE`
+    /// or a `DELETE` of a missing key replies `ERR_NOT_FOUND` and is skipped.
+    /// `MSET` and `MDELETE` reply with an array holding a reply per key.
+    /// The remaining ones are written as a single batch.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - The shared database
+    ///
+    /// # Returns
+    ///
+    /// * The reply of each queued command
+    ///
+    pub fn exec(self, db: &RwLock<Database>) -> Response {
+        let mut db = write_lock(db);
+        let mut live: HashMap<Vec<u8>, bool> = HashMap::new();
+        let mut operations: Vec<Operation> = Vec::new();
+        let mut replies: Vec<Response> = Vec::new();
+
+        for cmd in self.commands.iter() {
+            match *cmd.get_name() {
+                Type::MSet => {
+                    for pair in cmd.get_args().chunks(2) {
+                        operations.push(Operation::Set(
+                            pair[0].to_vec(),
+                            pair[1].to_vec(),
+                            0,
+                        ));
+                        live.insert(pair[0].to_vec(), true);
+                    }
+
+                    replies.push(Response::Array(vec![
+                        Response::ok();
+                        cmd.get_args().len() / 2
+                    ]));
+                }
+                Type::MDelete => {
+                    let mut deleted: Vec<Response> = Vec::new();
+
+                    for key in cmd.get_args().iter() {
+                        if exists(&db, &live, key) {
+                            operations.push(Operation::Delete(key.to_vec()));
+                            live.insert(key.to_vec(), false);
+                            deleted.push(Response::ok());
+                        } else {
+                            deleted.push(Response::not_found());
+                        }
+                    }
+
+                    replies.push(Response::Array(deleted));
+                }
+                Type::Update | Type::Delete if !exists(&db, &live, cmd.get_key()) => {
+                    replies.push(Response::not_found());
+                }
+                Type::Delete => {
+                    operations.push(Operation::Delete(cmd.get_key().to_vec()));
+                    live.insert(cmd.get_key().to_vec(), false);
+                    replies.push(Response::ok());
+                }
+                _ => {
+                    operations.push(Operation::Set(
+                        cmd.get_key().to_vec(),
+                        cmd.get_value().to_vec(),
+                        expire_at(*cmd.get_expire() as u64),
+                    ));
+                    live.insert(cmd.get_key().to_vec(), true);
+                    replies.push(Response::ok());
+                }
+            }
+        }
+
+        if operations.is_empty() {
+            return Response::Array(replies);
+        }
+
+        match db.apply(operations) {
+            Ok(_) => Response::Array(replies),
+            Err(e) => Response::from(e),
+        }
+    }
+}
+
+// Whether a key exists once the writes queued before are applied
+fn exists(db: &Database, live: &HashMap<Vec<u8>, bool>, key: &[u8]) -> bool {
+    match live.get(key) {
+        Some(exists) => *exists,
+        None => db.exists(key),
+    }
+}
+
+#[test]
+fn test_transaction() {
+    let _ = std::fs::remove_dir_all("./cache/transaction_exec");
+
+    let db = RwLock::new(Database::new("./cache/transaction_exec"));
+    let mut transaction: Transaction = Transaction::new();
+
+    assert!(transaction.is_empty());
+    assert!(!Transaction::accepts(
+        &Command::from_str("GET item1").unwrap()
+    ));
+
+    for cmd in [
+        "SET item1 value1",
+        "UPDATE item2 value2",
+        "UPDATE item1 value3",
+        "DELETE item3",
+        "SET item3 value4",
+        "DELETE item3",
+    ] {
+        let cmd = Command::from_str(cmd).unwrap();
+
+        assert!(Transaction::accepts(&cmd));
+        transaction.queue(cmd);
+    }
+
+    assert_eq!(transaction.len(), 6);
+    assert_eq!(
+        transaction.exec(&db),
+        Response::Array(vec![
+            Response::ok(),
+            Response::not_found(),
+            Response::ok(),
+            Response::not_found(),
+            Response::ok(),
+            Response::ok(),
+        ])
+    );
+
+    let db = db.read().unwrap();
+
+    assert_eq!(db.get("item1"), Ok(Some(b"value3".to_vec())));
+    assert_eq!(db.get("item2"), Ok(None));
+    assert_eq!(db.get("item3"), Ok(None));
+    assert_eq!(
+        Transaction::new().exec(&RwLock::new(Database::new(""))),
+        Response::Array(Vec::new())
+    );
+}
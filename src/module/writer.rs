@@ -2,6 +2,9 @@
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
 
+use crate::module::entry::LogEntry;
+use crate::module::error::Error;
+
 use std::fs::metadata;
 use std::fs::read_to_string;
 use std::fs::File;
@@ -9,6 +12,7 @@ use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
 
+#[derive(Default)]
 // Writer type
 pub struct Writer {}
 
@@ -37,13 +41,7 @@ impl Writer {
     /// * A boolean whether file exists or not
     ///
     pub fn file_exists(&self, path: String) -> bool {
-        let path = Path::new(path.as_str());
-
-        if path.exists() {
-            true
-        } else {
-            false
-        }
+        Path::new(path.as_str()).exists()
     }
 
     ///
@@ -58,15 +56,15 @@ impl Writer {
     ///
     /// * Error raised
     ///
-    pub fn overwrite(&self, path: String, content: String) -> Result<(), String> {
+    pub fn overwrite(&self, path: String, content: String) -> Result<(), Error> {
         let file = File::create(path.as_str());
 
         match file {
-            Ok(mut fi) => match fi.write_all(content.as_str().as_bytes()) {
-                Err(e) => Err(format!("Error raised: {}", e.to_string())),
+            Ok(mut fi) => match fi.write_all(content.as_bytes()) {
+                Err(e) => Err(Error::from(e)),
                 Ok(_) => Ok(()),
             },
-            Err(e) => Err(format!("Error raised: {}", e.to_string())),
+            Err(e) => Err(Error::from(e)),
         }
     }
 
@@ -82,19 +80,64 @@ impl Writer {
     ///
     /// * Error raised
     ///
-    pub fn append(&self, path: String, line: String) -> Result<(), String> {
-        let file = OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(path.to_string());
+    pub fn append(&self, path: String, line: String) -> Result<(), Error> {
+        let file = OpenOptions::new().append(true).create(true).open(path);
 
         match file {
-            Ok(mut fi) => match fi.write_all(line.as_str().as_bytes()) {
-                Err(e) => Err(format!("Error raised: {}", e.to_string())),
+            Ok(mut fi) => match fi.write_all(line.as_bytes()) {
+                Err(e) => Err(Error::from(e)),
                 Ok(_) => Ok(()),
             },
-            Err(e) => Err(format!("Error raised: {}", e.to_string())),
+            Err(e) => Err(Error::from(e)),
+        }
+    }
+
+    ///
+    /// Append a log entry to a datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the datafile
+    /// * `entry` - The log entry to append
+    ///
+    /// # Returns
+    ///
+    /// * The offset of the entry in the datafile
+    /// * Error raised
+    ///
+    pub fn append_entry(&self, path: String, entry: &LogEntry) -> Result<u64, Error> {
+        self.append_bytes(path, &entry.encode())
+    }
+
+    ///
+    /// Append encoded records to a datafile
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A string that holds the path to the datafile
+    /// * `buf` - The encoded records
+    ///
+    /// # Returns
+    ///
+    /// * The offset of the records in the datafile
+    /// * Error raised
+    ///
+    pub fn append_bytes(&self, path: String, buf: &[u8]) -> Result<u64, Error> {
+        let file = OpenOptions::new().append(true).create(true).open(path);
+
+        match file {
+            Ok(mut fi) => {
+                let offset = match fi.metadata() {
+                    Ok(meta) => meta.len(),
+                    Err(e) => return Err(Error::from(e)),
+                };
+
+                match fi.write_all(buf) {
+                    Err(e) => Err(Error::from(e)),
+                    Ok(_) => Ok(offset),
+                }
+            }
+            Err(e) => Err(Error::from(e)),
         }
     }
 
@@ -110,12 +153,12 @@ impl Writer {
     /// * The file content
     /// * Error raised
     ///
-    pub fn read(&self, path: String) -> Result<String, String> {
-        let fi = read_to_string(path.to_string());
+    pub fn read(&self, path: String) -> Result<String, Error> {
+        let fi = read_to_string(path);
 
         match fi {
             Ok(content) => Ok(content),
-            Err(err) => Err(format!("Error raised: {}", err.to_string())),
+            Err(err) => Err(Error::from(err)),
         }
     }
 
@@ -131,12 +174,12 @@ impl Writer {
     /// * The file size in bytes and error
     /// * Error raised
     ///
-    fn filesize(&self, path: String) -> Result<u64, String> {
-        let fi = metadata(path.to_string());
+    pub fn filesize(&self, path: String) -> Result<u64, Error> {
+        let fi = metadata(path);
 
         match fi {
             Ok(file) => Ok(file.len()),
-            Err(err) => Err(format!("Error raised: {}", err.to_string())),
+            Err(err) => Err(Error::from(err)),
         }
     }
 }
@@ -149,8 +192,8 @@ mod tests {
     /// test file_exists method
     fn test_file_exists() {
         let wt: Writer = Writer::new();
-        assert_eq!(wt.file_exists("cache/.gitignore".to_string()), true);
-        assert_eq!(wt.file_exists("cache/gitignore".to_string()), false);
+        assert!(wt.file_exists("cache/.gitignore".to_string()));
+        assert!(!wt.file_exists("cache/gitignore".to_string()));
     }
 
     #[test]
@@ -160,14 +203,7 @@ mod tests {
         let result =
             wt.overwrite("cache/test1.log".to_string(), "Hello World".to_string());
 
-        match result {
-            Ok(_) => {
-                assert!(true, "overwrite operation succeeded!");
-            }
-            Err(_) => {
-                assert!(false, "overwrite operation failed!");
-            }
-        }
+        assert!(result.is_ok(), "overwrite operation failed!");
     }
 
     #[test]
@@ -176,14 +212,7 @@ mod tests {
         let wt: Writer = Writer::new();
         let result = wt.append("cache/test2.log".to_string(), "Hello World".to_string());
 
-        match result {
-            Ok(_) => {
-                assert!(true, "append operation succeeded!");
-            }
-            Err(_) => {
-                assert!(false, "append operation failed!");
-            }
-        }
+        assert!(result.is_ok(), "append operation failed!");
     }
 
     #[test]
@@ -193,15 +222,11 @@ mod tests {
         let _ = wt.overwrite("cache/test4.log".to_string(), "Hello World".to_string());
         let result = wt.read("cache/test4.log".to_string());
 
-        match result {
-            Ok(data) => {
-                assert_eq!(data, "Hello World".to_string());
-                assert!(true, "read operation succeeded!");
-            }
-            Err(_) => {
-                assert!(false, "read operation failed!");
-            }
-        }
+        assert_eq!(
+            result,
+            Ok("Hello World".to_string()),
+            "read operation failed!"
+        );
     }
 
     #[test]
@@ -211,13 +236,29 @@ mod tests {
         let _ = wt.overwrite("cache/test3.log".to_string(), "Hello World".to_string());
         let result = wt.filesize("cache/test3.log".to_string());
 
-        match result {
-            Ok(size) => {
-                assert!(size > 1, "filesize operation succeeded!");
-            }
-            Err(_) => {
-                assert!(false, "filesize operation failed!");
-            }
-        }
+        assert!(result.is_ok(), "filesize operation failed!");
+        assert!(result.unwrap() > 1, "filesize operation failed!");
+    }
+
+    #[test]
+    /// test append_entry method
+    fn test_append_entry() {
+        let wt: Writer = Writer::new();
+        let _ = wt.overwrite("cache/test5.data".to_string(), "".to_string());
+
+        let entry = LogEntry::new("key1", "value1");
+
+        assert_eq!(
+            wt.append_entry("cache/test5.data".to_string(), &entry),
+            Ok(0)
+        );
+        assert_eq!(
+            wt.append_entry("cache/test5.data".to_string(), &entry),
+            Ok(entry.size())
+        );
+        assert_eq!(
+            wt.filesize("cache/test5.data".to_string()),
+            Ok(entry.size() * 2)
+        );
     }
 }
@@ -2,6 +2,10 @@
 // Use of this source code is governed by the MIT
 // license that can be found in the LICENSE file.
 
+use crate::module::error::Error;
+
+use std::str::FromStr;
+
 /// Returns the value of an environment variable
 ///
 /// # Arguments
@@ -11,9 +15,9 @@
 /// # Examples
 ///
 /// ```
-/// mod util;
+/// use langmore::util::environ::get_config;
 ///
-/// println!("CARGO_PKG_NAME = {}!", util::environ::get_config("CARGO_PKG_NAME"));
+/// println!("HOSTNAME = {}!", get_config("HOSTNAME", "127.0.0.1:8080"));
 /// ```
 pub fn get_config(key: &str, def: &str) -> String {
     match std::env::var(key) {
@@ -22,8 +26,46 @@ pub fn get_config(key: &str, def: &str) -> String {
     }
 }
 
+/// Returns the value of an environment variable parsed as a number or a flag
+///
+/// # Arguments
+///
+/// * `key` - environment variable name
+/// * `def` - the value used when the variable is not set
+///
+/// # Returns
+///
+/// * The parsed value
+/// * Error raised if the value can not be parsed
+///
+/// # Examples
+///
+/// ```
+/// use langmore::util::environ::parse_config;
+///
+/// let interval: u64 = parse_config("COMPACT_INTERVAL", "60").unwrap();
+/// ```
+pub fn parse_config<T: FromStr>(key: &str, def: &str) -> Result<T, Error> {
+    let value = get_config(key, def);
+
+    value
+        .parse()
+        .map_err(|_| Error::Config(format!("{} has an invalid value `{}`", key, value)))
+}
+
 #[test]
 fn test_get_config() {
     assert_eq!(get_config("CARGO_PKG_NAME", ""), "langmore");
     assert_eq!(get_config("CARGO__PKG_NAME", "default"), "default");
 }
+
+#[test]
+fn test_parse_config() {
+    assert_eq!(parse_config::<u64>("CARGO__PKG_SIZE", "60"), Ok(60));
+    assert_eq!(
+        parse_config::<u64>("CARGO_PKG_NAME", "60"),
+        Err(Error::Config(
+            "CARGO_PKG_NAME has an invalid value `langmore`".to_string()
+        ))
+    );
+}
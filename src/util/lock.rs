@@ -0,0 +1,65 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Takes the read lock, recovering it if a thread panicked while holding it
+///
+/// The database keeps its state consistent on every write error, so a
+/// panic in one connection should not take down the others.
+///
+/// # Arguments
+///
+/// * `lock` - The lock to take
+///
+/// # Examples
+///
+/// ```
+/// use langmore::util::lock::read_lock;
+/// use std::sync::RwLock;
+///
+/// assert_eq!(*read_lock(&RwLock::new(1)), 1);
+/// ```
+pub fn read_lock<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Takes the write lock, recovering it if a thread panicked while holding it
+///
+/// # Arguments
+///
+/// * `lock` - The lock to take
+///
+/// # Examples
+///
+/// ```
+/// use langmore::util::lock::write_lock;
+/// use std::sync::RwLock;
+///
+/// let lock = RwLock::new(1);
+/// *write_lock(&lock) += 1;
+///
+/// assert_eq!(*write_lock(&lock), 2);
+/// ```
+pub fn write_lock<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[test]
+fn test_poisoned_lock() {
+    let lock = std::sync::Arc::new(RwLock::new(1));
+    let other = std::sync::Arc::clone(&lock);
+
+    let _ = std::thread::spawn(move || {
+        let _guard = other.write().unwrap();
+        panic!("poison the lock");
+    })
+    .join();
+
+    assert!(lock.is_poisoned());
+
+    *write_lock(&lock) += 1;
+
+    assert_eq!(*read_lock(&lock), 2);
+}
@@ -3,3 +3,5 @@
 // license that can be found in the LICENSE file.
 
 pub mod environ;
+pub mod lock;
+pub mod pattern;
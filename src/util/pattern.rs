@@ -0,0 +1,73 @@
+// Copyright 2022 Clivern. All rights reserved.
+// Use of this source code is governed by the MIT
+// license that can be found in the LICENSE file.
+
+/// Whether a key matches a glob style pattern
+///
+/// `*` matches any sequence of bytes, `?` matches a single byte and `\`
+/// escapes the byte that follows it.
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern
+/// * `key` - The key bytes
+///
+/// # Examples
+///
+/// ```
+/// use langmore::util::pattern::matches;
+///
+/// assert!(matches(b"user:*", b"user:1"));
+/// ```
+pub fn matches(pattern: &[u8], key: &[u8]) -> bool {
+    let mut p = 0;
+    let mut k = 0;
+    // The position of the last `*` and of the key byte it matches up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while k < key.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, k));
+                p += 1;
+            }
+            Some(b'?') => {
+                p += 1;
+                k += 1;
+            }
+            Some(b'\\') if pattern.get(p + 1) == Some(&key[k]) => {
+                p += 2;
+                k += 1;
+            }
+            Some(c) if *c != b'\\' && *c == key[k] => {
+                p += 1;
+                k += 1;
+            }
+            _ => match star {
+                Some((sp, sk)) => {
+                    p = sp + 1;
+                    k = sk + 1;
+                    star = Some((sp, sk + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[test]
+fn test_matches() {
+    assert!(matches(b"*", b""));
+    assert!(matches(b"*", b"key1"));
+    assert!(matches(b"key?", b"key1"));
+    assert!(!matches(b"key?", b"key"));
+    assert!(matches(b"user:*:name", b"user:12:name"));
+    assert!(!matches(b"user:*:name", b"user:12:email"));
+    assert!(matches(b"*a*b", b"xaxxab"));
+    assert!(matches(b"key\\*", b"key*"));
+    assert!(!matches(b"key\\*", b"key1"));
+    assert!(!matches(b"key", b"key1"));
+    assert!(!matches(b"", b"key1"));
+}